@@ -1,136 +1,384 @@
 mod utils;
+mod tetris_bindings;
+mod tetris_board;
+mod tetris_clock;
+mod tetris_config;
 mod tetris_game;
+mod tetris_gamepad;
+mod tetris_gravity;
+mod tetris_handling;
+mod tetris_input;
 mod tetris_part;
 mod tetris_piece;
+mod tetris_randomizer;
+mod tetris_renderer;
+mod tetris_rng;
+mod tetris_rotation;
+mod tetris_scoring;
+mod tetris_spin;
+mod tetris_touch;
 
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use std::cell::RefCell;
 use std::rc::Rc;
-use crate::utils::{canvas, request_animation_frame, window, next_piece_canvas};
-use crate::tetris_game::{TetrisGame, NP_HEIGHT, NP_WIDTH};
+use crate::utils::{canvas, document, request_animation_frame, window, next_piece_canvas, hold_canvas, query_param, flag_param, local_storage, Listeners};
+use crate::tetris_bindings::{KeyBindings, RebindScreen, REBIND_KEY};
+use crate::tetris_clock::{FixedStep, TICK_MS};
+use crate::tetris_game::TetrisGame;
+use crate::tetris_gamepad::{GamepadPoller, PadReading};
+use crate::tetris_gravity::GravityCurve;
+use crate::tetris_handling::Handling;
+use crate::tetris_input::{Action, Controller, InputKind, InputQueue};
+use crate::tetris_randomizer::RandomizerKind;
+use crate::tetris_touch::TouchTracker;
+use crate::tetris_renderer::{TetrisRenderer, CANVAS_HEIGHT, CANVAS_WIDTH, HOLD_HEIGHT, HOLD_WIDTH, NP_HEIGHT, NP_WIDTH};
+use crate::tetris_config::{GameConfig, LockReset};
 use instant::Instant;
-use std::sync::{Arc, Mutex};
+use std::cell::Cell;
 
 
+thread_local! {
+    /// The page's event listeners, removed again if the game is started anew.
+    static LISTENERS: RefCell<Option<Listeners>> = const { RefCell::new(None) };
+}
+
+/// `localStorage` entry holding the player's key bindings.
+const BINDINGS_STORAGE_KEY: &str = "tetris.key_bindings";
+
+fn load_bindings() -> KeyBindings {
+    local_storage()
+        .and_then(|storage| storage.get_item(BINDINGS_STORAGE_KEY).ok().flatten())
+        .map(|text| KeyBindings::from_storage(&text))
+        .unwrap_or_default()
+}
+
+fn save_bindings(bindings: &KeyBindings) {
+    let saved = local_storage()
+        .map(|storage| storage.set_item(BINDINGS_STORAGE_KEY, &bindings.to_storage()).is_ok())
+        .unwrap_or(false);
+    if !saved {
+        log!("could not save the key bindings");
+    }
+}
+
+/// Passes a key or button press to the open rebinding screen, saving the
+/// bindings once the player closes it.
+fn press_on_rebind_screen(screen: &mut Option<RebindScreen>, code: &str, bindings: &mut KeyBindings) {
+    if let Some(open) = screen.as_mut() {
+        if !open.key(code, bindings) {
+            *screen = None;
+            save_bindings(bindings);
+        }
+    }
+}
+
+/// Reads every connected gamepad through `navigator.getGamepads()`.
+fn read_gamepads() -> Vec<PadReading> {
+    let pads = match window().navigator().get_gamepads() {
+        Ok(pads) => pads,
+        Err(_) => return Vec::new()
+    };
+    // unplugged slots are null
+    pads.iter()
+        .filter_map(|pad| pad.dyn_into::<web_sys::Gamepad>().ok())
+        .filter(|pad| pad.connected())
+        .map(|pad| PadReading {
+            buttons: pad.buttons().iter()
+                .map(|button| button.dyn_into::<web_sys::GamepadButton>().is_ok_and(|button| button.pressed()))
+                .collect(),
+            axes: pad.axes().iter().map(|axis| axis.as_f64().unwrap_or(0.0)).collect()
+        })
+        .collect()
+}
+
+/// Builds the game rules from the page URL, e.g. `?width=4&height=40&hidden_rows=2`,
+/// falling back to the defaults when the result is not a playable board.
+fn game_config() -> GameConfig {
+    let mut config = GameConfig::default();
+    let number = |name: &str| query_param(name).and_then(|value| value.parse().ok());
+    if let Some(randomizer) = query_param("randomizer").and_then(|name| RandomizerKind::from_name(&name)) {
+        config.randomizer = randomizer;
+    }
+    if let Some(width) = number("width") {
+        config.width = width;
+    }
+    if let Some(height) = number("height") {
+        config.visible_height = height;
+    }
+    if let Some(hidden_rows) = number("hidden_rows") {
+        config.hidden_rows = hidden_rows;
+    }
+    if let Some(half_turns) = flag_param("half_turns") {
+        config.half_turns = half_turns;
+    }
+    if let Some(lock_delay) = number("lock_delay") {
+        config.lock_delay = lock_delay as f64;
+    }
+    if let Some(lock_reset) = query_param("lock_reset").and_then(|name| LockReset::from_name(&name)) {
+        config.lock_reset = lock_reset;
+    }
+    if let Some(entry_delay) = number("entry_delay") {
+        config.entry_delay = entry_delay as f64;
+    }
+    if let Some(gravity) = query_param("gravity").and_then(|name| GravityCurve::from_name(&name)) {
+        config.gravity = gravity;
+    }
+    if let Some(level) = number("level") {
+        config.start_level = level.max(0) as usize;
+    }
+    if let Some(lines_per_level) = number("lines_per_level") {
+        config.lines_per_level = lines_per_level.max(0) as usize;
+    }
+    if let Err(message) = config.validate() {
+        log!("{}", message);
+        return GameConfig::default();
+    }
+    config
+}
+
+/// Reads the player's handling from the page URL, e.g. `?das=100&arr=0&sdf=40`.
+fn handling() -> Handling {
+    let mut handling = Handling::default();
+    let number = |name: &str| query_param(name).and_then(|value| value.parse().ok());
+    if let Some(das) = number("das") {
+        handling.das = das;
+    }
+    if let Some(arr) = number("arr") {
+        handling.arr = arr;
+    }
+    if let Some(soft_drop_factor) = number("sdf") {
+        handling.soft_drop_factor = soft_drop_factor;
+    }
+    if let Err(message) = handling.validate() {
+        log!("{}", message);
+        return Handling::default();
+    }
+    handling
+}
+
+/// Milliseconds since `epoch`, the clock input events and ticks share.
+fn millis_since(epoch: Instant) -> f64 {
+    epoch.elapsed().as_secs_f64() * 1000.0
+}
+
+/// Starts a game with the seed from the page URL, or a random one.
+fn new_game(config: &GameConfig) -> TetrisGame {
+    let seed = query_param("seed")
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| (js_sys::Math::random() * u32::MAX as f64) as u64);
+    let mut tetris = TetrisGame::new(seed, config.clone());
+    log!("seed: {}", tetris.seed());
+    tetris.start();
+    tetris
+}
+
 #[wasm_bindgen(start)]
 pub fn start() -> Result<(), JsValue> {
     let canvas : web_sys::HtmlCanvasElement = canvas();
-    canvas.set_width(600);
-    canvas.set_height(1000);
+    canvas.set_width(CANVAS_WIDTH);
+    canvas.set_height(CANVAS_HEIGHT);
 
     let next_pieces_canvas = next_piece_canvas();
     next_pieces_canvas.set_width(NP_WIDTH);
     next_pieces_canvas.set_height(NP_HEIGHT);
 
-    let mut tetris = TetrisGame::new();
-    tetris.tick();
+    let hold_canvas = hold_canvas();
+    hold_canvas.set_width(HOLD_WIDTH);
+    hold_canvas.set_height(HOLD_HEIGHT);
+
+    let config = game_config();
+    let mut tetris = new_game(&config);
+    let mut renderer = TetrisRenderer::new(&tetris.config);
+    renderer.show_ghost = flag_param("ghost").unwrap_or(true);
     let animate_cb = Rc::new(RefCell::new(None));
     let animate_cb2 = animate_cb.clone();
 
-    let mut last_tick_time = Instant::now();
+    let epoch = Instant::now();
+    let mut last_frame_time = 0.0;
+    let mut clock = FixedStep::new();
+    let mut controller = Controller::new(handling());
+    let mut gamepads = GamepadPoller::new();
 
-    let left = Arc::new(Mutex::new(false));
-    let left2 = Arc::clone(&left);
-    let left3 = Arc::clone(&left);
+    let inputs = Rc::new(RefCell::new(InputQueue::new()));
+    let inputs2 = Rc::clone(&inputs);
+    let inputs3 = Rc::clone(&inputs);
 
-    let right = Arc::new(Mutex::new(false));
-    let right2 = Arc::clone(&right);
-    let right3 = Arc::clone(&right);
+    let auto_pause = Rc::new(Cell::new(false));
+    let auto_pause2 = Rc::clone(&auto_pause);
+    let auto_pause3 = Rc::clone(&auto_pause);
 
-    let down = Arc::new(Mutex::new(false));
-    let down2 = Arc::clone(&down);
-    let down3 = Arc::clone(&down);
+    let focus_lost = Rc::new(Cell::new(false));
+    let focus_lost2 = Rc::clone(&focus_lost);
+    let focus_lost3 = Rc::clone(&focus_lost);
 
-    let space = Arc::new(Mutex::new(false));
-    let space2 = Arc::clone(&space);
+    let bindings = Rc::new(RefCell::new(load_bindings()));
+    let bindings2 = Rc::clone(&bindings);
+    let bindings3 = Rc::clone(&bindings);
 
-    let shift = Arc::new(Mutex::new(false));
-    let shift2 = Arc::clone(&shift);
-    let shift3 = Arc::clone(&shift);
+    let rebind_screen: Rc<RefCell<Option<RebindScreen>>> = Rc::new(RefCell::new(None));
+    let rebind_screen2 = Rc::clone(&rebind_screen);
 
-    *animate_cb2.borrow_mut() = Some(Closure::wrap(Box::new(move || {
-        let move_left = *left.lock().unwrap();
-        let move_right = *right.lock().unwrap();
-        let move_down = *down.lock().unwrap();
-        let rotate = *space.lock().unwrap();
-        let shift = *shift.lock().unwrap();
-        if tetris.clearing > 0 {
-            tetris.draw_clearing_rows();
-        } else if move_left {
-            tetris.move_left();
-            if !shift {
-                *left.lock().unwrap() = false;
-            }
-            tetris.draw();
-        } else if move_right {
-            tetris.move_right();
-            if !shift {
-                *right.lock().unwrap() = false;
-            }
-            tetris.draw();
-        } else if move_down {
-            tetris.move_down();
-            if !shift {
-                *down.lock().unwrap() = false;
-            }
-            tetris.draw();
-        } else if rotate {
-            tetris.rotate();
-            *space.lock().unwrap() = false;
-            tetris.draw();
-        } else {
-            let duration = last_tick_time.elapsed();
-            if duration.as_millis() > 1000 {
-                tetris.tick();
-                last_tick_time = Instant::now();
-            }
-        }
-        request_animation_frame(animate_cb.borrow().as_ref().unwrap());
-    }) as Box<dyn FnMut()>));
-    request_animation_frame(animate_cb2.borrow().as_ref().unwrap());
+    let mut listeners = Listeners::new();
 
     let key_down_closure = Closure::wrap(Box::new(move |event: web_sys::KeyboardEvent| {
+        if event.repeat() {
+            // held keys are tracked from press to release
+            return;
+        }
         let code = event.code();
-
-        if code == "ArrowLeft" {
-            *left2.lock().unwrap() = true;
-        } else if code == "ArrowRight" {
-            *right2.lock().unwrap() = true;
-        } else if code == "ArrowDown" {
-            *down2.lock().unwrap() = true;
-        } else if code == "ShiftLeft" {
-            *shift2.lock().unwrap() = true;
+        let mut screen = rebind_screen2.borrow_mut();
+        if screen.is_some() {
+            // keys on the rebinding screen never reach the game
+            event.prevent_default();
+            press_on_rebind_screen(&mut screen, &code, &mut bindings2.borrow_mut());
+        } else if code == REBIND_KEY {
+            event.prevent_default();
+            *screen = Some(RebindScreen::new());
+            auto_pause3.set(true);
+        } else if let Some(action) = bindings2.borrow().action_for(&code) {
+            inputs2.borrow_mut().push(action, InputKind::Press, millis_since(epoch));
         } else {
             log!("{}", code);
         }
-
     }) as Box<dyn FnMut(_)>);
 
-    window().add_event_listener_with_callback("keydown", key_down_closure.as_ref().unchecked_ref())?;
-    key_down_closure.forget();
+    listeners.listen(&[window().into()], &["keydown"], key_down_closure)?;
 
     let key_up_closure = Closure::wrap(Box::new(move |event: web_sys::KeyboardEvent| {
-        let code = event.code();
+        if let Some(action) = bindings3.borrow().action_for(&event.code()) {
+            inputs3.borrow_mut().push(action, InputKind::Release, millis_since(epoch));
+        }
+    }) as Box<dyn FnMut(_)>);
 
-        if code == "ArrowLeft" {
-            *left3.lock().unwrap() = false;
-        } else if code == "ArrowRight" {
-            *right3.lock().unwrap() = false;
-        } else if code == "ArrowDown" {
-            *down3.lock().unwrap() = false;
-        } else if code == "Space" {
-            *space2.lock().unwrap() = true;
-        } else if code == "ShiftLeft" {
-            *shift3.lock().unwrap() = false;
-        } else {
-            log!("{}", code);
+    listeners.listen(&[window().into()], &["keyup"], key_up_closure)?;
+
+    // the releases of keys held as the page loses focus go elsewhere
+    let visibility_closure = Closure::wrap(Box::new(move || {
+        if document().hidden() {
+            focus_lost2.set(true);
+            auto_pause2.set(true);
+        }
+    }) as Box<dyn FnMut()>);
+
+    listeners.listen(&[document().into()], &["visibilitychange"], visibility_closure)?;
+
+    let blur_closure = Closure::wrap(Box::new(move || {
+        focus_lost3.set(true);
+    }) as Box<dyn FnMut()>);
+
+    listeners.listen(&[window().into()], &["blur"], blur_closure)?;
+
+    let mut touches = TouchTracker::new();
+    let cell_size = renderer.cell_size();
+    let touch_canvas = canvas.clone();
+    let inputs4 = Rc::clone(&inputs);
+    let touch_closure = Closure::wrap(Box::new(move |event: web_sys::TouchEvent| {
+        // keeps the page from scrolling or zooming under the board
+        event.prevent_default();
+        let time = millis_since(epoch);
+        // the page layout scales the canvas
+        let cell_size = cell_size * touch_canvas.client_width() as f64 / CANVAS_WIDTH as f64;
+        let changed = event.changed_touches();
+        for touch in (0..changed.length()).filter_map(|index| changed.get(index)) {
+            let (id, x, y) = (touch.identifier(), touch.client_x() as f64, touch.client_y() as f64);
+            let actions = match event.type_().as_str() {
+                "touchstart" => {
+                    touches.start(id, x, y, time, cell_size);
+                    Vec::new()
+                }
+                "touchmove" => touches.move_to(id, x, y),
+                "touchend" => touches.end(id, x, y, time),
+                _ => {
+                    touches.cancel();
+                    Vec::new()
+                }
+            };
+            // gestures are taps, each action is pressed and let go at once
+            let mut inputs = inputs4.borrow_mut();
+            for action in actions {
+                inputs.push(action, InputKind::Press, time);
+                inputs.push(action, InputKind::Release, time);
+            }
         }
+    }) as Box<dyn FnMut(_)>);
+
+    listeners.listen(&[canvas.clone().into()], &["touchstart", "touchmove", "touchend", "touchcancel"], touch_closure)?;
 
+    // on-screen buttons name their action, e.g. `data-action="HardDrop"`,
+    // and are held like keys
+    let inputs5 = Rc::clone(&inputs);
+    let button_closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
+        event.prevent_default();
+        let action = event.current_target()
+            .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+            .and_then(|button| button.get_attribute("data-action"))
+            .and_then(|name| Action::from_name(&name));
+        if let Some(action) = action {
+            let kind = if event.type_() == "pointerdown" { InputKind::Press } else { InputKind::Release };
+            inputs5.borrow_mut().push(action, kind, millis_since(epoch));
+        }
     }) as Box<dyn FnMut(_)>);
 
-    window().add_event_listener_with_callback("keyup", key_up_closure.as_ref().unchecked_ref())?;
-    key_up_closure.forget();
+    let buttons = document().query_selector_all("[data-action]")?;
+    let buttons: Vec<web_sys::EventTarget> = (0..buttons.length()).filter_map(|index| buttons.get(index)).map(Into::into).collect();
+    listeners.listen(&buttons, &["pointerdown", "pointerup", "pointercancel", "pointerleave"], button_closure)?;
+    // shown on touch screens by the page style unless `?buttons=` says otherwise
+    if let (Some(show), Some(panel)) = (flag_param("buttons"), document().get_element_by_id("touch-buttons")) {
+        panel.set_attribute("data-show", if show { "1" } else { "0" })?;
+    }
+
+    // restarting swaps only the game, the listeners stay until the page
+    // starts the game anew
+    LISTENERS.with(|owner| owner.replace(Some(listeners)));
+
+    *animate_cb2.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+        if focus_lost.replace(false) {
+            inputs.borrow_mut().clear();
+            controller.release_all(&mut tetris);
+        }
+        if auto_pause.replace(false) {
+            tetris.pause();
+        }
+        // pads are polled once a frame, so their changes count from the
+        // start of it
+        let (pressed, released) = gamepads.poll(&read_gamepads());
+        for code in pressed {
+            let mut screen = rebind_screen.borrow_mut();
+            if screen.is_some() {
+                press_on_rebind_screen(&mut screen, &code, &mut bindings.borrow_mut());
+            } else if let Some(action) = bindings.borrow().action_for(&code) {
+                inputs.borrow_mut().push(action, InputKind::Press, last_frame_time);
+            }
+        }
+        for code in released {
+            if let Some(action) = bindings.borrow().action_for(&code) {
+                inputs.borrow_mut().push(action, InputKind::Release, last_frame_time);
+            }
+        }
+        let now = millis_since(epoch);
+        let ticks = clock.advance(now - last_frame_time);
+        last_frame_time = now;
+        // the game runs in fixed ticks whatever the display refresh rate,
+        // each tick takes the inputs that happened before it, in order
+        for tick in 0..ticks {
+            let tick_time = now - clock.alpha() * TICK_MS - (ticks - 1 - tick) as f64 * TICK_MS;
+            for event in inputs.borrow_mut().drain_until(tick_time) {
+                if event.action == Action::Restart && event.kind == InputKind::Press {
+                    tetris = new_game(&config);
+                } else {
+                    controller.apply(&mut tetris, &event);
+                }
+            }
+            controller.tick(&mut tetris);
+        }
+        renderer.draw(&tetris, clock.alpha(), &bindings.borrow());
+        if let Some(screen) = rebind_screen.borrow().as_ref() {
+            renderer.draw_rebind_screen(screen, &bindings.borrow());
+        }
+        request_animation_frame(animate_cb.borrow().as_ref().unwrap());
+    }) as Box<dyn FnMut()>));
+    request_animation_frame(animate_cb2.borrow().as_ref().unwrap());
 
     Ok(())
 }
\ No newline at end of file
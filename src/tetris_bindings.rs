@@ -0,0 +1,243 @@
+use crate::tetris_gamepad::control_label;
+use crate::tetris_input::Action;
+
+/// Opens and closes the rebinding screen. It can not be rebound, so the
+/// player always has a way back to it.
+pub(crate) const REBIND_KEY: &str = "F2";
+
+/// Keyboard and gamepad defaults, the pad ones in the standard mapping.
+const DEFAULT_BINDINGS: &[(Action, &[&str])] = &[
+    (Action::MoveLeft, &["ArrowLeft", "Gamepad14", "GamepadAxis0-"]),
+    (Action::MoveRight, &["ArrowRight", "Gamepad15", "GamepadAxis0+"]),
+    (Action::SoftDrop, &["ArrowDown", "Gamepad13", "GamepadAxis1+"]),
+    (Action::HardDrop, &["ArrowUp", "Gamepad12"]),
+    (Action::SonicDrop, &["KeyS"]),
+    (Action::RotateClockwise, &["Space", "KeyX", "Gamepad1"]),
+    (Action::RotateCounterclockwise, &["KeyZ", "ControlLeft", "Gamepad0"]),
+    (Action::Rotate180, &["KeyA", "Gamepad3"]),
+    (Action::Hold, &["KeyC", "Gamepad4", "Gamepad5"]),
+    (Action::Pause, &["Escape", "KeyP", "Gamepad9"]),
+    (Action::Restart, &["KeyR", "Gamepad8"])
+];
+
+/// The key codes (`KeyboardEvent.code`) and gamepad codes (see
+/// `tetris_gamepad`) bound to each action. A code belongs to at most one
+/// action.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct KeyBindings {
+    keys: Vec<(Action, Vec<String>)>
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            keys: DEFAULT_BINDINGS.iter()
+                .map(|(action, keys)| (*action, keys.iter().map(|key| key.to_string()).collect()))
+                .collect()
+        }
+    }
+}
+
+impl KeyBindings {
+
+    pub(crate) fn action_for(&self, code: &str) -> Option<Action> {
+        self.keys.iter()
+            .find(|(_, keys)| keys.iter().any(|key| key == code))
+            .map(|(action, _)| *action)
+    }
+
+    pub(crate) fn keys(&self, action: Action) -> &[String] {
+        self.keys.iter()
+            .find(|(bound, _)| *bound == action)
+            .map_or(&[], |(_, keys)| keys.as_slice())
+    }
+
+    /// The keys bound to `action` for an on-screen hint, e.g. `Escape/P`, or
+    /// `None` when it has no key.
+    pub(crate) fn key_hint(&self, action: Action) -> Option<String> {
+        let keys = self.keys(action);
+        if keys.is_empty() {
+            return None;
+        }
+        Some(keys.iter().map(|key| key_label(key)).collect::<Vec<&str>>().join("/"))
+    }
+
+    /// Adds `code` to the keys of `action`, taking it away from any other
+    /// action.
+    pub(crate) fn bind(&mut self, action: Action, code: &str) {
+        if code == REBIND_KEY {
+            return;
+        }
+        for (bound, keys) in self.keys.iter_mut() {
+            keys.retain(|key| key != code);
+            if *bound == action {
+                keys.push(code.to_string());
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self, action: Action) {
+        for (bound, keys) in self.keys.iter_mut() {
+            if *bound == action {
+                keys.clear();
+            }
+        }
+    }
+
+    /// Text kept in `localStorage`, e.g. `MoveLeft=ArrowLeft,KeyA;Hold=KeyC`.
+    pub(crate) fn to_storage(&self) -> String {
+        self.keys.iter()
+            .map(|(action, keys)| format!("{:?}={}", action, keys.join(",")))
+            .collect::<Vec<String>>()
+            .join(";")
+    }
+
+    /// Reads bindings saved by `to_storage`. Actions missing from the text
+    /// keep their default keys, unknown actions are skipped.
+    pub(crate) fn from_storage(text: &str) -> KeyBindings {
+        let mut bindings = KeyBindings::default();
+        for entry in text.split(';') {
+            let (name, codes) = match entry.split_once('=') {
+                Some(entry) => entry,
+                None => continue
+            };
+            if let Some(action) = Action::from_name(name) {
+                bindings.clear(action);
+                for code in codes.split(',').filter(|code| !code.is_empty()) {
+                    bindings.bind(action, code);
+                }
+            }
+        }
+        bindings
+    }
+}
+
+/// Short label for a key code, e.g. `X` for `KeyX`, `1` for `Digit1` and
+/// `Pad A` for `Gamepad0`.
+pub(crate) fn key_label(code: &str) -> &str {
+    if let Some(label) = control_label(code) {
+        return label;
+    }
+    code.strip_prefix("Key")
+        .or_else(|| code.strip_prefix("Digit"))
+        .unwrap_or(code)
+}
+
+/// The rebinding screen: a list of the actions with a cursor, waiting for
+/// a key to bind after Enter.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct RebindScreen {
+    pub(crate) selected: usize,
+    pub(crate) capturing: bool
+}
+
+impl RebindScreen {
+
+    pub(crate) fn new() -> RebindScreen {
+        RebindScreen {
+            selected: 0,
+            capturing: false
+        }
+    }
+
+    pub(crate) fn selected_action(&self) -> Action {
+        Action::ALL[self.selected]
+    }
+
+    /// Handles a key press on the screen and returns false once the player
+    /// closes it. Arrows move the cursor, Enter waits for a key to add,
+    /// Backspace unbinds and Escape or `REBIND_KEY` closes the screen.
+    pub(crate) fn key(&mut self, code: &str, bindings: &mut KeyBindings) -> bool {
+        if self.capturing {
+            self.capturing = false;
+            if code != "Escape" {
+                bindings.bind(self.selected_action(), code);
+            }
+            return true;
+        }
+        match code {
+            "ArrowUp" => self.selected = (self.selected + Action::ALL.len() - 1) % Action::ALL.len(),
+            "ArrowDown" => self.selected = (self.selected + 1) % Action::ALL.len(),
+            "Enter" => self.capturing = true,
+            "Backspace" | "Delete" => bindings.clear(self.selected_action()),
+            "Escape" | REBIND_KEY => return false,
+            _ => {}
+        }
+        true
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_a_key_moves_it_between_actions() {
+        let mut bindings = KeyBindings::default();
+        assert_eq!(bindings.action_for("KeyX"), Some(Action::RotateClockwise));
+        bindings.bind(Action::Hold, "KeyX");
+        assert_eq!(bindings.action_for("KeyX"), Some(Action::Hold));
+        assert_eq!(bindings.keys(Action::RotateClockwise), ["Space", "Gamepad1"]);
+        assert_eq!(bindings.keys(Action::Hold), ["KeyC", "Gamepad4", "Gamepad5", "KeyX"]);
+        bindings.bind(Action::Hold, REBIND_KEY);
+        assert_eq!(bindings.action_for(REBIND_KEY), None);
+    }
+
+    #[test]
+    fn bindings_survive_storage() {
+        let mut bindings = KeyBindings::default();
+        bindings.clear(Action::MoveLeft);
+        bindings.bind(Action::MoveLeft, "KeyA");
+        bindings.bind(Action::MoveRight, "KeyD");
+        let text = bindings.to_storage();
+        assert_eq!(KeyBindings::from_storage(&text), bindings);
+        assert_eq!(bindings.action_for("KeyA"), Some(Action::MoveLeft));
+        assert_eq!(bindings.keys(Action::Rotate180), ["Gamepad3"]);
+    }
+
+    #[test]
+    fn unknown_or_missing_entries_keep_the_defaults() {
+        let bindings = KeyBindings::from_storage("Fly=KeyF;Hold=ShiftLeft;garbage");
+        assert_eq!(bindings.keys(Action::Hold), ["ShiftLeft"]);
+        assert_eq!(bindings.action_for("ArrowLeft"), Some(Action::MoveLeft));
+        assert_eq!(bindings.action_for("KeyF"), None);
+    }
+
+    #[test]
+    fn screen_captures_the_next_key() {
+        let mut bindings = KeyBindings::default();
+        let mut screen = RebindScreen::new();
+        assert!(screen.key("ArrowUp", &mut bindings));
+        assert_eq!(screen.selected_action(), Action::Restart);
+        screen.key("ArrowDown", &mut bindings);
+        screen.key("Backspace", &mut bindings);
+        screen.key("Enter", &mut bindings);
+        // while capturing, navigation keys are bound like any other
+        screen.key("ArrowUp", &mut bindings);
+        assert_eq!(bindings.keys(Action::MoveLeft), ["ArrowUp"]);
+        assert_eq!(bindings.action_for("ArrowUp"), Some(Action::MoveLeft));
+        screen.key("Enter", &mut bindings);
+        screen.key("Escape", &mut bindings);
+        assert_eq!(bindings.keys(Action::MoveLeft), ["ArrowUp"]);
+        assert!(!screen.key("Escape", &mut bindings));
+    }
+
+    #[test]
+    fn hints_follow_the_bindings() {
+        let mut bindings = KeyBindings::default();
+        assert_eq!(bindings.key_hint(Action::Restart), Some("R/Pad Back".to_string()));
+        bindings.clear(Action::Restart);
+        assert_eq!(bindings.key_hint(Action::Restart), None);
+        bindings.bind(Action::Restart, "KeyN");
+        assert_eq!(bindings.key_hint(Action::Restart), Some("N".to_string()));
+    }
+
+    #[test]
+    fn key_labels() {
+        assert_eq!(key_label("KeyX"), "X");
+        assert_eq!(key_label("Digit1"), "1");
+        assert_eq!(key_label("ArrowLeft"), "ArrowLeft");
+        assert_eq!(key_label("Gamepad9"), "Pad Start");
+    }
+}
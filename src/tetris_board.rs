@@ -0,0 +1,152 @@
+use crate::tetris_piece::{TetrisPiece, TetrisPieceType};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct BoardCell {
+    pub(crate) piece_type: TetrisPieceType,
+    pub(crate) color: &'static str
+}
+
+/// The locked cells of the playfield. Each row is kept as a bitmask (bit `x`
+/// set when column `x` is filled) next to a flat array of cell details, so
+/// occupancy checks never touch the cell array.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Board {
+    width: i64,
+    height: i64,
+    full_row: u64,
+    rows: Vec<u64>,
+    cells: Vec<Option<BoardCell>>
+}
+
+impl Board {
+
+    pub(crate) fn new(width: i64, height: i64) -> Board {
+        assert!(width > 0 && width <= 64, "board width must be between 1 and 64");
+        assert!(height > 0, "board height must be positive");
+        Board {
+            width,
+            height,
+            full_row: u64::MAX >> (64 - width),
+            rows: vec![0; height as usize],
+            cells: vec![None; (width * height) as usize]
+        }
+    }
+
+    pub(crate) fn width(&self) -> i64 {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> i64 {
+        self.height
+    }
+
+    fn in_bounds(&self, x: i64, y: i64) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    /// Cells outside the board count as occupied so walls and floor collide.
+    pub(crate) fn is_occupied(&self, x: i64, y: i64) -> bool {
+        !self.in_bounds(x, y) || self.rows[y as usize] & (1 << x) != 0
+    }
+
+    pub(crate) fn cell(&self, x: i64, y: i64) -> Option<BoardCell> {
+        if self.in_bounds(x, y) {
+            self.cells[(y * self.width + x) as usize]
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn collides(&self, piece: &TetrisPiece) -> bool {
+        piece.parts.iter().any(|part| self.is_occupied(part.x, part.y))
+    }
+
+    /// Writes the piece into the board. Parts outside the board are dropped.
+    pub(crate) fn lock(&mut self, piece: &TetrisPiece) {
+        for part in &piece.parts {
+            if self.in_bounds(part.x, part.y) {
+                self.rows[part.y as usize] |= 1 << part.x;
+                self.cells[(part.y * self.width + part.x) as usize] = Some(BoardCell {
+                    piece_type: piece.piece_type,
+                    color: piece.color
+                });
+            }
+        }
+    }
+
+    pub(crate) fn is_row_full(&self, y: i64) -> bool {
+        self.rows[y as usize] == self.full_row
+    }
+
+    /// True when no cell is occupied, e.g. after a perfect clear.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+
+    /// Indices of all full rows, top to bottom.
+    pub(crate) fn full_rows(&self) -> Vec<i64> {
+        (0..self.height).filter(|y| self.is_row_full(*y)).collect()
+    }
+
+    /// Removes row `y` and shifts every row above it down by one.
+    pub(crate) fn remove_row(&mut self, y: i64) {
+        let y = y as usize;
+        let width = self.width as usize;
+        self.rows.copy_within(0..y, 1);
+        self.rows[0] = 0;
+        self.cells.copy_within(0..y * width, width);
+        for cell in &mut self.cells[0..width] {
+            *cell = None;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece_at(x: i64, y: i64) -> TetrisPiece {
+        TetrisPiece::new(TetrisPieceType::Q, x, y, "#ffffff")
+    }
+
+    #[test]
+    fn outside_cells_are_occupied() {
+        let board = Board::new(10, 22);
+        assert!(board.is_occupied(-1, 0));
+        assert!(board.is_occupied(10, 0));
+        assert!(board.is_occupied(0, 22));
+        assert!(!board.is_occupied(0, 0));
+        assert!(!board.is_occupied(9, 21));
+    }
+
+    #[test]
+    fn lock_and_collide() {
+        let mut board = Board::new(10, 22);
+        let piece = piece_at(4, 20);
+        assert!(!board.collides(&piece));
+        assert!(board.is_empty());
+        board.lock(&piece);
+        assert!(board.collides(&piece));
+        assert!(board.is_occupied(5, 21));
+        assert_eq!(board.cell(5, 21).map(|c| c.piece_type), Some(TetrisPieceType::Q));
+        assert!(!board.collides(&piece_at(6, 20)));
+    }
+
+    #[test]
+    fn full_rows_are_removed_and_shifted() {
+        let mut board = Board::new(4, 6);
+        board.lock(&piece_at(0, 4));
+        board.lock(&piece_at(2, 4));
+        board.lock(&piece_at(0, 2));
+        assert_eq!(board.full_rows(), vec![4, 5]);
+        board.remove_row(4);
+        board.remove_row(5);
+        assert!(board.full_rows().is_empty());
+        assert!(!board.is_empty());
+        assert!(board.is_occupied(0, 5));
+        assert!(board.is_occupied(1, 4));
+        assert!(!board.is_occupied(2, 5));
+        assert!(!board.is_occupied(0, 3));
+    }
+}
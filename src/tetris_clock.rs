@@ -0,0 +1,78 @@
+use crate::tetris_gravity::FRAME_RATE;
+
+/// Length of one simulation tick in milliseconds.
+pub(crate) const TICK_MS: f64 = 1000.0 / FRAME_RATE;
+
+/// Ticks run at most per frame. A longer gap, e.g. from a stalled tab, is
+/// dropped instead of fast forwarding through it.
+const MAX_TICKS_PER_FRAME: usize = 10;
+
+/// Splits real frame times into whole fixed ticks so the game runs at the
+/// same speed on every display, carrying the remainder to the next frame.
+pub(crate) struct FixedStep {
+    accumulator: f64
+}
+
+impl FixedStep {
+
+    pub(crate) fn new() -> FixedStep {
+        FixedStep {
+            accumulator: 0.0
+        }
+    }
+
+    /// Adds `elapsed` milliseconds and returns the number of ticks to run.
+    pub(crate) fn advance(&mut self, elapsed: f64) -> usize {
+        self.accumulator += elapsed.max(0.0);
+        // a hair of slack so frame times that add up to whole ticks are not
+        // lost to rounding
+        let ticks = ((self.accumulator + 1e-6) / TICK_MS) as usize;
+        if ticks > MAX_TICKS_PER_FRAME {
+            self.accumulator = 0.0;
+            return MAX_TICKS_PER_FRAME;
+        }
+        self.accumulator = (self.accumulator - ticks as f64 * TICK_MS).max(0.0);
+        ticks
+    }
+
+    /// How far the next tick is along, from 0 to 1, for drawing in between.
+    pub(crate) fn alpha(&self) -> f64 {
+        self.accumulator / TICK_MS
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs frames of `frame_ms` for one simulated second.
+    fn ticks_per_second(frame_ms: f64) -> usize {
+        let mut clock = FixedStep::new();
+        let frames = (1000.0 / frame_ms).round() as usize;
+        (0..frames).map(|_| clock.advance(frame_ms)).sum()
+    }
+
+    #[test]
+    fn tick_rate_does_not_depend_on_the_display() {
+        assert_eq!(ticks_per_second(1000.0 / 60.0), 60);
+        assert_eq!(ticks_per_second(1000.0 / 144.0), 60);
+        assert_eq!(ticks_per_second(1000.0 / 30.0), 60);
+    }
+
+    #[test]
+    fn remainder_carries_over_as_alpha() {
+        let mut clock = FixedStep::new();
+        assert_eq!(clock.advance(TICK_MS / 2.0), 0);
+        assert!((clock.alpha() - 0.5).abs() < 1e-9);
+        assert_eq!(clock.advance(TICK_MS / 2.0), 1);
+        assert!(clock.alpha() < 1e-9);
+    }
+
+    #[test]
+    fn long_stalls_are_dropped() {
+        let mut clock = FixedStep::new();
+        assert_eq!(clock.advance(5000.0), MAX_TICKS_PER_FRAME);
+        assert_eq!(clock.alpha(), 0.0);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::tetris_gravity::GravityCurve;
+use crate::tetris_randomizer::RandomizerKind;
+
+/// What restarts the lock delay of a piece resting on the stack. Falling to
+/// a new lowest row always restarts it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum LockReset {
+    /// Every move or rotation restarts the delay.
+    Infinite,
+    /// Moves and rotations restart the delay this many times per piece,
+    /// after which the piece locks as soon as it touches down. Reaching a
+    /// new lowest row refills the count.
+    Limited(u32),
+    /// Only falling restarts the delay.
+    Step
+}
+
+impl LockReset {
+
+    /// Parses `infinite`, `step` or `limited`, optionally followed by the
+    /// number of resets like `limited8`.
+    pub(crate) fn from_name(name: &str) -> Option<LockReset> {
+        match name {
+            "infinite" => Some(LockReset::Infinite),
+            "step" => Some(LockReset::Step),
+            "limited" => Some(LockReset::Limited(15)),
+            _ => name.strip_prefix("limited").and_then(|count| count.parse().ok()).map(LockReset::Limited)
+        }
+    }
+}
+
+/// Rules a game is created with. Everything here is fixed for the lifetime
+/// of a `TetrisGame`.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct GameConfig {
+    /// Columns on the board.
+    pub(crate) width: i64,
+    /// Rows shown to the player.
+    pub(crate) visible_height: i64,
+    /// Rows above the visible playfield where pieces spawn and may rest but
+    /// are never drawn.
+    pub(crate) hidden_rows: i64,
+    pub(crate) randomizer: RandomizerKind,
+    /// End the game when a piece locks partly in the hidden rows, not only
+    /// when it locks entirely inside them.
+    pub(crate) partial_lock_out: bool,
+    /// Allow 180 degree rotations.
+    pub(crate) half_turns: bool,
+    /// Milliseconds a piece may rest on the stack before it locks.
+    pub(crate) lock_delay: f64,
+    pub(crate) lock_reset: LockReset,
+    /// Milliseconds between a piece locking and the next one entering.
+    pub(crate) entry_delay: f64,
+    pub(crate) gravity: GravityCurve,
+    /// Level the game starts at, the first level is 1.
+    pub(crate) start_level: usize,
+    /// Lines to clear for each level up.
+    pub(crate) lines_per_level: usize
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            width: 10,
+            visible_height: 22,
+            hidden_rows: 20,
+            randomizer: RandomizerKind::SevenBag,
+            partial_lock_out: false,
+            half_turns: false,
+            lock_delay: 500.0,
+            lock_reset: LockReset::Limited(15),
+            entry_delay: 0.0,
+            gravity: GravityCurve::Guideline,
+            start_level: 1,
+            lines_per_level: 10
+        }
+    }
+}
+
+impl GameConfig {
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.width < 4 || self.width > 64 {
+            return Err(format!("width must be between 4 and 64, got {}", self.width));
+        }
+        if self.visible_height < 4 {
+            return Err(format!("visible height must be at least 4, got {}", self.visible_height));
+        }
+        if self.hidden_rows < 0 {
+            return Err(format!("hidden rows can not be negative, got {}", self.hidden_rows));
+        }
+        if self.lock_delay < 0.0 {
+            return Err(format!("lock delay can not be negative, got {}", self.lock_delay));
+        }
+        if self.entry_delay < 0.0 {
+            return Err(format!("entry delay can not be negative, got {}", self.entry_delay));
+        }
+        if self.start_level < 1 {
+            return Err(format!("start level must be at least 1, got {}", self.start_level));
+        }
+        if self.lines_per_level < 1 {
+            return Err(format!("lines per level must be at least 1, got {}", self.lines_per_level));
+        }
+        Ok(())
+    }
+
+    /// Rows on the board, hidden rows included.
+    pub(crate) fn height(&self) -> i64 {
+        self.visible_height + self.hidden_rows
+    }
+
+    /// Top row of a freshly spawned piece: the two rows just above the
+    /// visible playfield, or the top of the board without enough hidden rows.
+    pub(crate) fn spawn_y(&self) -> i64 {
+        (self.hidden_rows - 2).max(0)
+    }
+
+    /// Left-most column of a freshly spawned piece, centering the 4 wide
+    /// spawn box on the board.
+    pub(crate) fn spawn_x(&self) -> i64 {
+        (self.width - 4) / 2
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_valid() {
+        assert_eq!(GameConfig::default().validate(), Ok(()));
+        assert_eq!(GameConfig::default().spawn_x(), 3);
+        assert_eq!(GameConfig::default().spawn_y(), 18);
+        assert_eq!(GameConfig::default().height(), 42);
+    }
+
+    #[test]
+    fn rejects_boards_pieces_cannot_fit() {
+        let narrow = GameConfig { width: 3, ..GameConfig::default() };
+        assert!(narrow.validate().is_err());
+        let wide = GameConfig { width: 65, ..GameConfig::default() };
+        assert!(wide.validate().is_err());
+        let short = GameConfig { visible_height: 3, ..GameConfig::default() };
+        assert!(short.validate().is_err());
+        let hidden = GameConfig { hidden_rows: -1, ..GameConfig::default() };
+        assert!(hidden.validate().is_err());
+        let drill = GameConfig { width: 4, visible_height: 40, hidden_rows: 0, ..GameConfig::default() };
+        assert_eq!(drill.validate(), Ok(()));
+        assert_eq!(drill.spawn_x(), 0);
+        assert_eq!(drill.spawn_y(), 0);
+        let instant_lock = GameConfig { lock_delay: -1.0, ..GameConfig::default() };
+        assert!(instant_lock.validate().is_err());
+        let level_zero = GameConfig { start_level: 0, ..GameConfig::default() };
+        assert!(level_zero.validate().is_err());
+        let no_level_up = GameConfig { lines_per_level: 0, ..GameConfig::default() };
+        assert!(no_level_up.validate().is_err());
+    }
+
+    #[test]
+    fn lock_reset_names() {
+        assert_eq!(LockReset::from_name("infinite"), Some(LockReset::Infinite));
+        assert_eq!(LockReset::from_name("step"), Some(LockReset::Step));
+        assert_eq!(LockReset::from_name("limited"), Some(LockReset::Limited(15)));
+        assert_eq!(LockReset::from_name("limited8"), Some(LockReset::Limited(8)));
+        assert_eq!(LockReset::from_name("sometimes"), None);
+    }
+}
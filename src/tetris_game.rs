@@ -1,25 +1,14 @@
-use std::collections::HashMap;
-use crate::utils::{context, next_piece_context, score};
+use std::mem;
+use crate::tetris_board::Board;
+use crate::tetris_clock::TICK_MS;
+use crate::tetris_config::{GameConfig, LockReset};
+use crate::tetris_gravity::{FRAME_RATE, MAX_GRAVITY};
 use crate::tetris_piece::{TetrisPiece, TetrisPieceType};
-use crate::tetris_part::TetrisPart;
-
-
-
-const H_CELLS: i64 = 10;
-const V_CELLS: i64 = 22;
-const H_CELL_SIZE: f64 = 600.0 / (H_CELLS as f64);
-const V_CELL_SIZE: f64 = 1000.0 / (V_CELLS as f64);
-pub const NP_HEIGHT: u32 = 400;
-pub const NP_WIDTH: u32 = 150;
-const NP_SECTION_HEIGHT: f64 = NP_HEIGHT as f64 / 3.0;
-const NP_V_CELL_SIZE: f64 = NP_SECTION_HEIGHT as f64 / 4.0;
-const NP_H_CELL_SIZE: f64 = NP_WIDTH as f64 / 4.0;
-
-macro_rules! log {
-    ( $( $t:tt )* ) => {
-        web_sys::console::log_1(&format!( $( $t )* ).into());
-    }
-}
+use crate::tetris_randomizer::Randomizer;
+use crate::tetris_rng::Rng;
+use crate::tetris_rotation::kicks;
+use crate::tetris_scoring::ClearAction;
+use crate::tetris_spin::{t_spin, TSpin};
 
 const PINK: &str = "#cd00cd";
 const RED: &str = "#ff0000";
@@ -29,653 +18,1244 @@ const ORANGE: &str = "#ff7800";
 const LIGHT_BLUE: &str = "#00ffff";
 const DARK_BLUE: &str = "#0000ac";
 
+/// Length of the line clear animation, counted down by `CLEARING_STEP`
+/// every tick.
+pub(crate) const CLEARING: i64 = 200;
+pub(crate) const CLEARING_STEP: i64 = 3;
+
+/// Why a game ended, following the guideline top out conditions.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(clippy::enum_variant_names)]
+pub(crate) enum TopOut {
+    /// The next piece spawned overlapping the stack.
+    BlockOut,
+    /// A piece locked entirely above the visible playfield.
+    LockOut,
+    /// A piece locked partly above the visible playfield.
+    PartialLockOut
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum GameState {
+    Playing,
+    /// Nothing moves or times out until the game is resumed.
+    Paused,
+    GameOver(TopOut)
+}
+
 pub(crate) struct TetrisGame {
-    pub(crate) grid: HashMap<String, usize>,
-    pub(crate) pieces: Vec<TetrisPiece>,
-    pub(crate) piece_bag: Vec<TetrisPieceType>,
-    pub(crate) color_bag: Vec<String>,
-    pub(crate) active_piece: i64,
+    pub(crate) config: GameConfig,
+    pub(crate) state: GameState,
+    pub(crate) board: Board,
+    pub(crate) color_bag: Vec<&'static str>,
+    pub(crate) active_piece: Option<TetrisPiece>,
     pub(crate) next_pieces: Vec<TetrisPiece>,
+    /// Piece put aside with `hold`, kept in spawn orientation.
+    pub(crate) hold_piece: Option<TetrisPiece>,
+    /// Only one hold is allowed per piece taken from the queue.
+    pub(crate) can_hold: bool,
     pub(crate) score: i64,
     pub(crate) clearing: i64,
-    cleared_rows: Vec<i64>,
-    level: usize
+    pub(crate) cleared_rows: Vec<i64>,
+    /// Milliseconds the active piece has been resting on the stack.
+    lock_timer: f64,
+    /// Lock delay restarts used up by moves and rotations of this piece.
+    lock_resets: u32,
+    /// Lowest row the active piece has reached, used to refill lock resets.
+    lowest_y: i64,
+    /// Rows gravity has yet to move the active piece down, carried between
+    /// updates so gravity below 1G adds up.
+    fall_progress: f64,
+    /// Gravity multiplier while the player soft drops, 1 otherwise.
+    soft_drop: f64,
+    /// Milliseconds left before the next piece enters.
+    entry_timer: f64,
+    /// Quarter turns of the rotation key held down and whether the hold key
+    /// is, as last told by `set_initial_actions`.
+    held_turns: i64,
+    held_hold: bool,
+    /// Rotation and hold asked for between pieces, during a line clear or
+    /// the entry delay, applied to the next piece as it spawns.
+    initial_turns: i64,
+    initial_hold: bool,
+    /// Quarter turns and kick index of the last rotation, cleared by any
+    /// move since. Only a piece locked right after rotating can T-spin.
+    last_rotation: Option<(i64, usize)>,
+    /// T-spin made by the piece that just locked, scored with its lines.
+    t_spin: Option<TSpin>,
+    /// What the last piece to clear lines or T-spin achieved.
+    pub(crate) last_action: Option<ClearAction>,
+    /// The last difficult clear has not been followed by an easy one yet.
+    pub(crate) back_to_back: bool,
+    /// Clearing placements in a row after the first, `None` once a piece
+    /// locks without clearing.
+    pub(crate) combo: Option<usize>,
+    /// Line clears that left the board empty this game.
+    pub(crate) perfect_clears: usize,
+    pub(crate) level: usize,
+    /// Lines cleared this game.
+    pub(crate) lines: usize,
+    randomizer: Box<dyn Randomizer>,
+    rng: Rng
 }
 
 impl TetrisGame {
 
-    fn draw_next_pieces(&mut self) {
-        let ctx = next_piece_context();
-        ctx.set_fill_style(&"#071428".into());
-        ctx.fill_rect(0.0, 0.0, NP_WIDTH as f64, NP_HEIGHT as f64);
-
-        let mut i = 0.0;
-        for piece in &self.next_pieces {
-            let piece_parts = piece.parts.clone();
-            let start = i * NP_SECTION_HEIGHT;
-            for part in piece_parts {
-                let x = part.x - 3;
-                let y = part.y;
-
-                ctx.set_fill_style(&piece.color.clone().into());
-                let x_start = (x as f64 * NP_H_CELL_SIZE) + 20.0;
-                let y_start = (y as f64 * NP_V_CELL_SIZE) + start + 20.0;
-                ctx.fill_rect(x_start, y_start, NP_H_CELL_SIZE, NP_V_CELL_SIZE);
-            }
-            i += 1.0;
-        }
-    }
-
-    pub(crate) fn new() -> TetrisGame {
+    /// Two games created with the same seed and config deal the same
+    /// pieces and colors.
+    pub(crate) fn new(seed: u64, config: GameConfig) -> TetrisGame {
         let mut t = TetrisGame {
-            grid: Default::default(),
-            pieces: Default::default(),
-            piece_bag: TetrisGame::new_piece_type_bag(),
+            state: GameState::Playing,
+            board: Board::new(config.width, config.height()),
             color_bag: TetrisGame::new_color_bag(),
-            active_piece: -1,
+            active_piece: None,
             next_pieces: vec![],
-            level: 1,
+            hold_piece: None,
+            can_hold: true,
+            level: config.start_level,
+            lines: 0,
             score: 0,
             clearing: 0,
-            cleared_rows: vec![]
+            cleared_rows: vec![],
+            lock_timer: 0.0,
+            lock_resets: 0,
+            lowest_y: 0,
+            fall_progress: 0.0,
+            soft_drop: 1.0,
+            entry_timer: 0.0,
+            held_turns: 0,
+            held_hold: false,
+            initial_turns: 0,
+            initial_hold: false,
+            last_rotation: None,
+            t_spin: None,
+            last_action: None,
+            back_to_back: false,
+            combo: None,
+            perfect_clears: 0,
+            randomizer: config.randomizer.create(),
+            rng: Rng::new(seed),
+            config
         };
 
         for _i in 0..3 {
             let item = t.next_piece();
             let color = t.next_color();
-            let next_piece = TetrisPiece::new(item, 3, color);
+            let next_piece = TetrisPiece::new(item, t.config.spawn_x(), t.config.spawn_y(), color);
             t.next_pieces.push(next_piece);
         }
         t
     }
 
     fn next_piece(&mut self) -> TetrisPieceType {
-        let index = (js_sys::Math::random() * (self.piece_bag.len() as f64)).floor() as usize;
-        let value = self.piece_bag.remove(index);
-        if self.piece_bag.len() == 0 {
-            self.piece_bag = TetrisGame::new_piece_type_bag();
-        }
-        value
+        self.randomizer.next(&mut self.rng)
     }
 
-    fn next_color(&mut self) -> String {
-        let index = (js_sys::Math::random() * (self.color_bag.len() as f64)).floor() as usize;
+    fn next_color(&mut self) -> &'static str {
+        let index = self.rng.next_index(self.color_bag.len());
         let value = self.color_bag.remove(index);
-        if self.color_bag.len() == 0 {
+        if self.color_bag.is_empty() {
             self.color_bag = TetrisGame::new_color_bag();
         }
         value
     }
 
-    fn add_piece(&mut self, piece: TetrisPiece) {
-        let piece_num = self.pieces.len();
-
-        for part in &piece.parts {
-            let key = format!("{},{}", part.x.to_string(), part.y.to_string());
-            self.grid.insert(key, piece_num);
-        }
-
-        self.pieces.push(piece);
-
-        self.active_piece = piece_num as i64;
+    pub(crate) fn seed(&self) -> u64 {
+        self.rng.seed()
     }
 
-    pub(crate) fn draw(&mut self) {
-        TetrisGame::draw_game_board();
-        self.draw_pieces();
+    /// Brings in the first piece. From then on the game runs through `step`.
+    pub(crate) fn start(&mut self) {
+        self.spawn_when_ready();
     }
 
-    pub(crate) fn draw_clearing_rows(&mut self) {
-        let context = context();
-        let r = 255 - self.clearing;
-        let g = 213 - self.clearing;
-        let b = 0;
-        let color = format!("rgb({}, {}, {})", r, g, b);
-        log!("drawing: {}", color);
-        context.set_fill_style(&color.into());
-        for line in &self.cleared_rows {
-            log!("{}", line);
-            let x_start = 0.0;
-            let y_start =  *line as f64 * V_CELL_SIZE;
-            context.fill_rect(x_start, y_start, H_CELLS as f64 * H_CELL_SIZE, V_CELL_SIZE);
+    /// Advances the game clock by `elapsed` milliseconds: the active piece
+    /// falls at the gravity of the current level and locks once it has
+    /// rested on the stack for the lock delay.
+    pub(crate) fn update(&mut self, elapsed: f64) {
+        if self.state != GameState::Playing {
+            return;
         }
-        self.clearing -= 3;
-    }
-
-    pub(crate) fn tick(&mut self) {
-        if self.active_piece == -1 {
-            let mut lines_cleared = vec![];
-            loop {
-                let cleared_line = self.check_lines();
-                if cleared_line == -1 {
-                    break;
-                }
-                lines_cleared.push(cleared_line);
+        if self.active_piece.is_none() {
+            self.entry_timer -= elapsed;
+            self.spawn_when_ready();
+            return;
+        }
+        let gravity = self.config.gravity.gravity(self.level) * self.soft_drop;
+        // rows fallen while soft dropping are worth a point each
+        let points = if self.soft_drop > 1.0 { 1 } else { 0 };
+        if gravity >= MAX_GRAVITY {
+            // 20G, the piece reaches the stack the moment it appears
+            let distance = self.drop_distance();
+            if distance > 0 {
+                self.try_move(0, distance);
+                self.score += points * distance;
             }
-            if lines_cleared.len() == 4 {
-                // tetris
-                self.score += 800 * self.level as i64;
-            } else if lines_cleared.len() == 3 {
-                self.score += 500 * self.level as i64;
-            } else if lines_cleared.len() == 2 {
-                self.score += 300 * self.level as i64;
-            } else if lines_cleared.len() == 1 {
-                self.score += 100 * self.level as i64;
+        } else {
+            self.fall_progress += gravity * elapsed * FRAME_RATE / 1000.0;
+        }
+        while self.fall_progress >= 1.0 {
+            self.fall_progress -= 1.0;
+            if self.try_move(0, 1) {
+                self.score += points;
+            } else {
+                self.fall_progress = 0.0;
             }
-            if lines_cleared.len() > 0 {
-                self.cleared_rows = lines_cleared;
-                self.clearing = 200;
+        }
+        if self.drop_distance() > 0 {
+            return;
+        }
+        self.lock_timer += elapsed;
+        let out_of_resets = match self.config.lock_reset {
+            LockReset::Limited(max) => self.lock_resets >= max,
+            _ => false
+        };
+        if self.lock_timer >= self.config.lock_delay || out_of_resets {
+            if let Some(piece) = self.active_piece.take() {
+                self.lock_and_spawn(piece);
             }
-            let next = self.next_pieces.remove(0);
-            let item = self.next_piece();
-            let color = self.next_color();
-            self.next_pieces.push(TetrisPiece::new(item, 3, color));
-            self.add_piece(next);
-            self.draw_next_pieces();
-            self.draw_score();
-            self.draw_clearing_rows();
-        } else {
-            self.move_down();
         }
-        // self.log_state();
-        self.draw();
     }
 
-    fn draw_score(&mut self) {
-        score().set_inner_text(&format!("{}", self.score));
+    /// Advances the game by one fixed tick: the line clear animation while
+    /// it runs, gravity and lock delay otherwise.
+    pub(crate) fn step(&mut self) {
+        if self.clearing > 0 {
+            self.step_clearing();
+        } else {
+            self.update(TICK_MS);
+        }
     }
 
-    pub(crate) fn move_down(&mut self) {
-        if self.active_piece == -1 {
-            return;
+    /// Clears and scores the rows completed by the last locked piece,
+    /// together with any T-spin it made.
+    fn score_lines(&mut self) {
+        let lines_cleared = self.clear_lines();
+        let mut action = ClearAction {
+            lines: lines_cleared.len(),
+            t_spin: self.t_spin.take(),
+            back_to_back: false,
+            perfect_clear: !lines_cleared.is_empty() && self.board.is_empty()
+        };
+        action.back_to_back = self.back_to_back && action.is_difficult();
+        if action.perfect_clear {
+            self.perfect_clears += 1;
         }
-        let mut piece = self.pieces.remove(self.active_piece as usize);
-        let mut new_parts = vec![];
-        let mut can_move = true;
-        for part in &piece.parts {
-            let next_key = format!("{},{}", part.x, part.y + 1);
-            if self.grid.contains_key(&next_key) {
-                if let Some(piece_index) = self.grid.remove(&next_key) {
-                    self.grid.insert(next_key, piece_index);
-                    if piece_index as i64 != self.active_piece {
-                        can_move = false;
-                    }
-                }
-            }
-            if (part.y + 1) >= V_CELLS {
-                can_move = false;
-            }
+        if action.lines > 0 || action.t_spin.is_some() {
+            self.score += action.points() * self.level as i64;
+            self.last_action = Some(action);
         }
-        if can_move {
-            for part in &piece.parts {
-                let current_key = format!("{},{}", part.x, part.y);
-                self.grid.remove(&current_key);
-            }
-            for part in piece.parts {
-                let next_key = format!("{},{}", part.x, part.y + 1);
-                self.grid.insert(next_key, self.pieces.len());
-                new_parts.push(TetrisPart {
-                    x: part.x,
-                    y: part.y + 1,
-                    visible: part.visible
-                })
-            }
-            piece.parts = new_parts;
-            self.active_piece = self.pieces.len() as i64;
+        if action.lines > 0 {
+            self.back_to_back = action.is_difficult();
+            let combo = self.combo.map_or(0, |combo| combo + 1);
+            self.score += 50 * (combo * self.level) as i64;
+            self.combo = Some(combo);
         } else {
-            self.active_piece = -1;
+            self.combo = None;
+        }
+        if !lines_cleared.is_empty() {
+            self.lines += lines_cleared.len();
+            self.level = self.config.start_level + self.lines / self.config.lines_per_level;
+            self.cleared_rows = lines_cleared;
+            self.clearing = CLEARING;
         }
-        self.pieces.push(piece);
     }
 
-    fn draw_pieces(&mut self) {
-        let context = context();
-        for piece in &self.pieces {
-            context.set_fill_style(&piece.color.clone().into());
-            for part in &piece.parts {
-                if !part.visible {
-                    continue
-                }
-                let x_start = (part.x as f64) * H_CELL_SIZE;
-                let y_start = (part.y as f64) * V_CELL_SIZE;
-                context.fill_rect(x_start, y_start, H_CELL_SIZE, V_CELL_SIZE);
-            }
+    /// Brings in the next piece once the line clear animation and the entry
+    /// delay are over.
+    fn spawn_when_ready(&mut self) {
+        if self.state == GameState::Playing && self.active_piece.is_none()
+            && self.clearing <= 0 && self.entry_timer <= 0.0 {
+            self.spawn_next();
         }
     }
 
-    fn new_piece_type_bag() -> Vec<TetrisPieceType> {
-        vec![
-            TetrisPieceType::Q,
-            TetrisPieceType::Z,
-            TetrisPieceType::S,
-            TetrisPieceType::T,
-            TetrisPieceType::I,
-            TetrisPieceType::L,
-            TetrisPieceType::J
-        ]
+    fn take_next(&mut self) -> TetrisPiece {
+        let next = self.next_pieces.remove(0);
+        let item = self.next_piece();
+        let color = self.next_color();
+        self.next_pieces.push(TetrisPiece::new(item, self.config.spawn_x(), self.config.spawn_y(), color));
+        next
     }
 
-    fn new_color_bag() -> Vec<String> {
-        vec![
-            PINK.to_string(), RED.to_string(), YELLOW.to_string(), GREEN.to_string(), ORANGE.to_string(), LIGHT_BLUE.to_string(), DARK_BLUE.to_string()
-        ]
+    /// Tells the game which rotation and hold keys are held down. Between
+    /// pieces they turn or hold the next piece as it spawns.
+    pub(crate) fn set_initial_actions(&mut self, turns: i64, hold: bool) {
+        self.held_turns = turns.rem_euclid(4);
+        self.held_hold = hold;
+        if self.active_piece.is_none() {
+            self.buffer_held_actions();
+        }
     }
 
-    fn draw_game_board() {
-        let context = context();
-        context.set_fill_style(&"#000712".into());
-        context.fill_rect(0.0, 0.0, 600.0, 1000.0);
+    /// Keeps the rotation and hold keys held between pieces for the next
+    /// spawn, so a key let go before it enters still counts.
+    fn buffer_held_actions(&mut self) {
+        if self.held_turns != 0 {
+            self.initial_turns = self.held_turns;
+        }
+        self.initial_hold |= self.held_hold;
+    }
+
+    /// Spawns the next piece from the queue, holding it straight away or
+    /// turning it, when the turned piece fits, if the player asked for that
+    /// between pieces.
+    fn spawn_next(&mut self) {
+        let mut next = self.take_next();
+        self.can_hold = true;
+        if mem::take(&mut self.initial_hold) {
+            self.can_hold = false;
+            next = match self.hold_piece.replace(next) {
+                Some(held) => held,
+                None => self.take_next()
+            };
+        }
+        let turns = mem::take(&mut self.initial_turns);
+        let turns = if turns == 2 && !self.config.half_turns { 0 } else { turns };
+        let turned = next.rotated(next.rotation + turns);
+        if turns != 0 && !self.board.collides(&turned) {
+            next = turned;
+        }
+        self.enter(next);
+    }
+
+    /// Makes a freshly spawned piece the active piece, or ends the game when
+    /// the spawn cells are taken.
+    fn enter(&mut self, piece: TetrisPiece) {
+        if self.board.collides(&piece) {
+            self.state = GameState::GameOver(TopOut::BlockOut);
+        } else {
+            self.lock_timer = 0.0;
+            self.lock_resets = 0;
+            self.lowest_y = piece.y;
+            self.fall_progress = 0.0;
+            self.last_rotation = None;
+            self.active_piece = Some(piece);
+            // pieces spawn above the playfield and drop straight into view
+            self.try_move(0, 1);
+        }
+    }
+
+    /// Swaps the active piece with the held one, or puts it aside and takes
+    /// the next piece when nothing is held yet.
+    pub(crate) fn hold(&mut self) {
+        if self.state != GameState::Playing {
+            return;
+        }
+        let piece = match self.active_piece.take() {
+            Some(piece) => piece,
+            None => return
+        };
+        if !self.can_hold {
+            self.active_piece = Some(piece);
+            return;
+        }
+        let reset = TetrisPiece::new(piece.piece_type, self.config.spawn_x(), self.config.spawn_y(), piece.color);
+        match self.hold_piece.replace(reset) {
+            Some(held) => self.enter(held),
+            None => {
+                let next = self.take_next();
+                self.enter(next);
+            }
+        }
+        self.can_hold = false;
+    }
 
-        context.set_stroke_style(&"#0654df".into());
-        for n in 0..V_CELLS+1 {
-            context.begin_path();
-            context.move_to(0.0, V_CELL_SIZE * (n as f64));
-            context.line_to(600.0, V_CELL_SIZE * (n as f64));
-            context.stroke();
+    pub(crate) fn pause(&mut self) {
+        if self.state == GameState::Playing {
+            self.state = GameState::Paused;
         }
+    }
 
-        for n in 0..H_CELLS+1 {
-            context.begin_path();
-            context.move_to(H_CELL_SIZE * (n as f64), 0.0);
-            context.line_to(H_CELL_SIZE * (n as f64), 1000.0);
-            context.stroke();
+    pub(crate) fn resume(&mut self) {
+        if self.state == GameState::Paused {
+            self.state = GameState::Playing;
         }
     }
 
-    #[allow(dead_code)]
-    fn log_state(&mut self) {
-        log!("{}, num pieces: {}", "logging state", self.pieces.len());
-        log!("{:?}", self.grid);
+    pub(crate) fn toggle_pause(&mut self) {
+        if self.state == GameState::Paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
     }
 
-    pub(crate) fn move_left(&mut self) {
-        if self.active_piece == -1 {
+    /// Advances the row clearing animation, the next piece enters when it
+    /// is over.
+    pub(crate) fn step_clearing(&mut self) {
+        if self.state != GameState::Playing {
             return;
         }
+        self.clearing -= CLEARING_STEP;
+        self.spawn_when_ready();
+    }
 
-        let active_piece = self.active_piece.clone();
-        let mut piece = self.pieces.remove(active_piece as usize);
-        let mut new_parts = vec![];
-        let mut can_move_left = true;
-        for part in &piece.parts {
-            let next_key = format!("{},{}", part.x - 1, part.y);
-            if self.grid.contains_key(&next_key) {
-                if let Some(piece_index) = self.grid.remove(&next_key) {
-                    self.grid.insert(next_key, piece_index);
-                    if piece_index as i64 != active_piece {
-                        can_move_left = false;
-                    }
-                }
-            }
-            if (part.x - 1) < 0 {
-                can_move_left = false;
-            }
+    /// Moves the active piece if the board has room for it and reports
+    /// whether it moved.
+    fn try_move(&mut self, dx: i64, dy: i64) -> bool {
+        if self.state != GameState::Playing {
+            return false;
         }
-        if can_move_left {
-            for part in &piece.parts {
-                let current_key = format!("{},{}", part.x, part.y);
-                self.grid.remove(&current_key);
+        if let Some(piece) = &self.active_piece {
+            let moved = piece.shifted(dx, dy);
+            if !self.board.collides(&moved) {
+                self.place(moved);
+                self.last_rotation = None;
+                return true;
             }
-            for part in piece.parts {
-                let next_key = format!("{},{}", part.x - 1, part.y);
-                self.grid.insert(next_key, self.pieces.len());
-                new_parts.push(TetrisPart {
-                    x: part.x - 1,
-                    y: part.y,
-                    visible: part.visible
-                })
-            }
-            piece.parts = new_parts;
         }
-        self.active_piece = self.pieces.len() as i64;
-        self.pieces.push(piece);
+        false
     }
 
-    pub(crate) fn move_right(&mut self) {
-        if self.active_piece == -1 {
-            return;
+    /// Replaces the active piece with a moved copy of it, restarting the lock
+    /// delay when it reached a new lowest row.
+    fn place(&mut self, piece: TetrisPiece) {
+        if piece.y > self.lowest_y {
+            self.lowest_y = piece.y;
+            self.lock_timer = 0.0;
+            self.lock_resets = 0;
         }
+        self.active_piece = Some(piece);
+    }
 
-        let active_piece = self.active_piece.clone();
-        let mut piece = self.pieces.remove(active_piece as usize);
-        let mut new_parts = vec![];
-        let mut can_move = true;
-        for part in &piece.parts {
-            let next_key = format!("{},{}", part.x + 1, part.y);
-            if self.grid.contains_key(&next_key) {
-                if let Some(piece_index) = self.grid.remove(&next_key) {
-                    self.grid.insert(next_key, piece_index);
-                    if piece_index as i64 != active_piece {
-                        can_move = false;
-                    }
+    /// Restarts the lock delay after a successful move or rotation, as far
+    /// as the lock reset rule allows.
+    fn reset_lock_delay(&mut self) {
+        match self.config.lock_reset {
+            LockReset::Infinite => self.lock_timer = 0.0,
+            LockReset::Limited(max) => {
+                if self.lock_timer > 0.0 && self.lock_resets < max {
+                    self.lock_timer = 0.0;
+                    self.lock_resets += 1;
                 }
             }
-            if (part.x + 1) >= H_CELLS {
-                can_move = false;
-            }
+            LockReset::Step => {}
         }
-        if can_move {
-            for part in &piece.parts {
-                let current_key = format!("{},{}", part.x, part.y);
-                self.grid.remove(&current_key);
-            }
-            for part in piece.parts {
-                let next_key = format!("{},{}", part.x + 1, part.y);
-                self.grid.insert(next_key, self.pieces.len());
-                new_parts.push(TetrisPart {
-                    x: part.x + 1,
-                    y: part.y,
-                    visible: part.visible
-                })
-            }
-            piece.parts = new_parts;
+    }
+
+    /// Starts soft dropping at `factor` times the gravity of the level, or
+    /// stops it with a factor of 1.
+    pub(crate) fn set_soft_drop(&mut self, factor: f64) {
+        self.soft_drop = factor;
+    }
+
+    /// Rows the active piece can fall before it lands.
+    pub(crate) fn drop_distance(&self) -> i64 {
+        let piece = match &self.active_piece {
+            Some(piece) => piece,
+            None => return 0
+        };
+        let mut distance = 0;
+        while !self.board.collides(&piece.shifted(0, distance + 1)) {
+            distance += 1;
         }
-        self.active_piece = self.pieces.len() as i64;
-        self.pieces.push(piece);
+        distance
     }
 
-    pub(crate) fn rotate(&mut self) {
-        if self.active_piece == -1 {
+    /// Where the active piece would land if hard dropped now.
+    pub(crate) fn ghost_piece(&self) -> Option<TetrisPiece> {
+        let distance = self.drop_distance();
+        self.active_piece.as_ref().map(|piece| piece.shifted(0, distance))
+    }
+
+    /// Drops the active piece onto the stack, locks it and spawns the next
+    /// piece. Worth 2 points per row.
+    pub(crate) fn hard_drop(&mut self) {
+        if self.state != GameState::Playing {
             return;
         }
-        let mut piece = self.pieces.remove(self.active_piece as usize);
-        macro_rules! tetris_part {
-            ($part:expr, $x: expr, $y: expr) => {
-                TetrisPart {
-                    x: piece.parts[$part].x + $x,
-                    y: piece.parts[$part].y + $y,
-                    visible: piece.parts[$part].visible
-                }
+        let distance = self.drop_distance();
+        if let Some(piece) = self.active_piece.take() {
+            self.score += 2 * distance;
+            if distance > 0 {
+                self.last_rotation = None;
             }
+            self.lock_and_spawn(piece.shifted(0, distance));
         }
-        let original_rotation = piece.rotation.clone();
-        for part in &piece.parts {
-            let current_key = format!("{},{}", part.x, part.y);
-            self.grid.remove(&current_key);
+    }
+
+    /// Moves the active piece onto the stack without locking it. Scores like
+    /// a soft drop over the same rows.
+    pub(crate) fn sonic_drop(&mut self) {
+        let distance = self.drop_distance();
+        if distance > 0 && self.try_move(0, distance) {
+            self.score += distance;
         }
-        let new_parts = match piece.piece_type {
-            TetrisPieceType::Q => {
-                piece.parts.clone()
-            }
-            TetrisPieceType::Z => {
-                if piece.rotation == 1 {
-                    piece.rotation = 2;
-                    vec![
-                        tetris_part! (0, 2, 0),
-                        tetris_part! (1, 1, 1),
-                        tetris_part! (2, 0, 0),
-                        tetris_part! (3, -1, 1)
-                    ]
-                } else if piece.rotation == 2 {
-                    piece.rotation = 3;
-                    vec![
-                        tetris_part! (0, 0, 2),
-                        tetris_part! (1, -1, 1),
-                        tetris_part! (2, 0, 0),
-                        tetris_part! (3, -1, -1)
-                    ]
-                } else if piece.rotation == 3 {
-                    piece.rotation = 4;
-                    vec![
-                        tetris_part! (0, -2, 0),
-                        tetris_part! (1, -1, -1),
-                        tetris_part! (2, 0, 0),
-                        tetris_part! (3, 1, -1)
-                    ]
-                } else {
-                    // rotation == 4
-                    piece.rotation = 1;
-                    vec![
-                        tetris_part! (0, 0, -2),
-                        tetris_part! (1, 1, -1),
-                        tetris_part! (2, 0, 0),
-                        tetris_part! (3, 1, 1)
-                    ]
-                }
-            }
-            TetrisPieceType::S => {
-                if piece.rotation == 1 {
-                    piece.rotation = 2;
-                    vec![
-                        tetris_part! (0, 1, 1),
-                        tetris_part! (1, 0, 2),
-                        tetris_part! (2, 1, -1),
-                        tetris_part! (3, 0, 0)
-                    ]
-                } else if piece.rotation == 2 {
-                    piece.rotation = 3;
-                    vec![
-                        tetris_part! (0, -1, 1),
-                        tetris_part! (1, -2, 0),
-                        tetris_part! (2, 1, 1),
-                        tetris_part! (3, 0, 0)
-                    ]
-                } else if piece.rotation == 3 {
-                    piece.rotation = 4;
-                    vec![
-                        tetris_part! (0, -1, -1),
-                        tetris_part! (1, 0, -2),
-                        tetris_part! (2, -1, 1),
-                        tetris_part! (3, 0, 0)
-                    ]
-                } else {
-                    piece.rotation = 1;
-                    vec![
-                        tetris_part! (0, 1, -1),
-                        tetris_part! (1, 2, 0),
-                        tetris_part! (2, -1, -1),
-                        tetris_part! (3, 0, 0)
-                    ]
-                }
-            }
-            TetrisPieceType::T => {
-                if piece.rotation == 1 {
-                    piece.rotation = 2;
-                    vec![
-                        tetris_part! (0, 1, -1),
-                        tetris_part! (1, 0, 0),
-                        tetris_part! (2, -1, 1),
-                        tetris_part! (3, 1, 1)
-                    ]
-                } else if piece.rotation == 2 {
-                    piece.rotation = 3;
-                    vec![
-                        tetris_part! (0, 1, 1),
-                        tetris_part! (1, 0, 0),
-                        tetris_part! (2, -1, -1),
-                        tetris_part! (3, -1, 1)
-                    ]
-                } else if piece.rotation == 3 {
-                    piece.rotation = 4;
-                    vec![
-                        tetris_part! (0, -1, 1),
-                        tetris_part! (1, 0, 0),
-                        tetris_part! (2, 1, -1),
-                        tetris_part! (3, -1, -1)
-                    ]
-                } else {
-                    piece.rotation = 1;
-                    vec![
-                        tetris_part! (0, -1, -1),
-                        tetris_part! (1, 0, 0),
-                        tetris_part! (2, 1, 1),
-                        tetris_part! (3, 1, -1)
-                    ]
-                }
-            }
-            TetrisPieceType::I => {
-                if piece.rotation == 1 {
-                    piece.rotation = 2;
-                    vec![
-                        tetris_part! (0, 2, -1),
-                        tetris_part! (1, 1, 0),
-                        tetris_part! (2, 0, 1),
-                        tetris_part! (3, -1, 2)
-                    ]
-                } else if piece.rotation == 2 {
-                    piece.rotation = 3;
-                    vec![
-                        tetris_part! (0, 1, 2),
-                        tetris_part! (1, 0, 1),
-                        tetris_part! (2, -1, 0),
-                        tetris_part! (3, -2, -1)
-                    ]
-                } else if piece.rotation == 3 {
-                    piece.rotation = 4;
-                    vec![
-                        tetris_part! (0, -2, 1),
-                        tetris_part! (1, -1, 0),
-                        tetris_part! (2, 0, -1),
-                        tetris_part! (3, 1, -2)
-                    ]
-                } else {
-                    piece.rotation = 1;
-                    vec![
-                        tetris_part! (0, -1, -2),
-                        tetris_part! (1, 0, -1),
-                        tetris_part! (2, 1, 0),
-                        tetris_part! (3, 2, 1)
-                    ]
-                }
-            }
-            TetrisPieceType::L => {
-                if piece.rotation == 1 {
-                    piece.rotation = 2;
-                    vec![
-                        tetris_part! (0, 1, -1),
-                        tetris_part! (1, 0, 0),
-                        tetris_part! (2, -1, 1),
-                        tetris_part! (3, 0, 2)
-                    ]
-                } else if piece.rotation == 2 {
-                    piece.rotation = 3;
-                    vec![
-                        tetris_part! (0, 1, 1),
-                        tetris_part! (1, 0, 0),
-                        tetris_part! (2, -1, -1),
-                        tetris_part! (3, -2, 0)
-                    ]
-                } else if piece.rotation == 3 {
-                    piece.rotation = 4;
-                    vec![
-                        tetris_part! (0, -1, 1),
-                        tetris_part! (1, 0, 0),
-                        tetris_part! (2, 1, -1),
-                        tetris_part! (3, 0, -2)
-                    ]
-                } else {
-                    piece.rotation = 1;
-                    vec![
-                        tetris_part! (0, -1, -1),
-                        tetris_part! (1, 0, 0),
-                        tetris_part! (2, 1, 1),
-                        tetris_part! (3, 2, 0)
-                    ]
-                }
+    }
+
+    fn lock_and_spawn(&mut self, piece: TetrisPiece) {
+        self.t_spin = match self.last_rotation.take() {
+            // the last SRS kick of a quarter turn is the TST kick
+            Some((turns, kick)) => t_spin(&self.board, &piece, turns != 2 && kick == 4),
+            None => None
+        };
+        self.lock_piece(piece);
+        if self.state == GameState::Playing {
+            self.score_lines();
+            self.entry_timer = self.config.entry_delay;
+            // keys still down from the last piece only count when the next
+            // one waits for a line clear or the entry delay
+            if self.clearing > 0 || self.entry_timer > 0.0 {
+                self.buffer_held_actions();
             }
-            TetrisPieceType::J => {
-                if piece.rotation == 1 {
-                    piece.rotation = 2;
-                    vec![
-                        tetris_part! (0, 2, 0),
-                        tetris_part! (1, 1, -1),
-                        tetris_part! (2, 0, 0),
-                        tetris_part! (3, -1, 1)
-                    ]
-                } else if piece.rotation == 2 {
-                    piece.rotation = 3;
-                    vec![
-                        tetris_part! (0, 0, 2),
-                        tetris_part! (1, 1, 1),
-                        tetris_part! (2, 0, 0),
-                        tetris_part! (3, -1, -1)
-                    ]
-                } else if piece.rotation == 3 {
-                    piece.rotation = 4;
-                    vec![
-                        tetris_part! (0, -2, 0),
-                        tetris_part! (1, -1, 1),
-                        tetris_part! (2, 0, 0),
-                        tetris_part! (3, 1, -1)
-                    ]
-                } else {
-                    piece.rotation = 1;
-                    vec![
-                        tetris_part! (0, 0, -2),
-                        tetris_part! (1, -1, -1),
-                        tetris_part! (2, 0, 0),
-                        tetris_part! (3, 1, 1)
-                    ]
-                }
+            self.spawn_when_ready();
+        }
+    }
+
+    fn lock_piece(&mut self, piece: TetrisPiece) {
+        let hidden_rows = self.config.hidden_rows;
+        let hidden_parts = piece.parts.iter().filter(|part| part.y < hidden_rows).count();
+        self.board.lock(&piece);
+        if hidden_parts == piece.parts.len() {
+            self.state = GameState::GameOver(TopOut::LockOut);
+        } else if hidden_parts > 0 && self.config.partial_lock_out {
+            self.state = GameState::GameOver(TopOut::PartialLockOut);
+        }
+    }
+
+    pub(crate) fn move_left(&mut self) -> bool {
+        let moved = self.try_move(-1, 0);
+        if moved {
+            self.reset_lock_delay();
+        }
+        moved
+    }
+
+    pub(crate) fn move_right(&mut self) -> bool {
+        let moved = self.try_move(1, 0);
+        if moved {
+            self.reset_lock_delay();
+        }
+        moved
+    }
+
+    fn new_color_bag() -> Vec<&'static str> {
+        vec![
+            PINK, RED, YELLOW, GREEN, ORANGE, LIGHT_BLUE, DARK_BLUE
+        ]
+    }
+
+    pub(crate) fn rotate_clockwise(&mut self) {
+        self.rotate(1);
+    }
+
+    pub(crate) fn rotate_counterclockwise(&mut self) {
+        self.rotate(3);
+    }
+
+    /// Does nothing unless half turns are enabled in the config.
+    pub(crate) fn rotate_180(&mut self) {
+        if self.config.half_turns {
+            self.rotate(2);
+        }
+    }
+
+    /// Turns the active piece `turns` quarter turns clockwise using the SRS
+    /// kick tests. The O piece keeps its place and state.
+    fn rotate(&mut self, turns: i64) {
+        if self.state != GameState::Playing {
+            return;
+        }
+        let piece = match &self.active_piece {
+            Some(piece) => piece,
+            None => return
+        };
+        if piece.piece_type == TetrisPieceType::Q {
+            return;
+        }
+        let rotated = piece.rotated(piece.rotation + turns);
+        for (kick, (dx, dy)) in kicks(piece.piece_type, piece.rotation, turns).iter().enumerate() {
+            let kicked = rotated.shifted(*dx, *dy);
+            if !self.board.collides(&kicked) {
+                self.place(kicked);
+                self.last_rotation = Some((turns, kick));
+                self.reset_lock_delay();
+                return;
             }
+        }
+    }
+
+    /// Removes every full row and returns their indices, top to bottom.
+    fn clear_lines(&mut self) -> Vec<i64> {
+        let rows = self.board.full_rows();
+        for y in &rows {
+            self.board.remove_row(*y);
+        }
+        rows
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_gravity::GravityCurve;
+    use crate::tetris_part::TetrisPart;
+
+    const SEED: u64 = 1234;
+
+    fn new_game() -> TetrisGame {
+        TetrisGame::new(SEED, GameConfig::default())
+    }
+
+    fn fill_row(game: &mut TetrisGame, y: i64, skip: &[i64]) {
+        let row = TetrisPiece {
+            parts: (0..game.board.width()).filter(|x| !skip.contains(x)).map(|x| TetrisPart::new(x, y)).collect(),
+            color: PINK,
+            piece_type: TetrisPieceType::I,
+            rotation: 0,
+            x: 0,
+            y
         };
+        game.board.lock(&row);
+    }
 
-        let mut can_rotate = true;
-        for part in &new_parts {
-            if part.x >= H_CELLS || part.x < 0 || part.y >= V_CELLS {
-                can_rotate = false;
-                break;
-            }
+    fn active_parts(game: &TetrisGame) -> Vec<TetrisPart> {
+        game.active_piece.as_ref().unwrap().parts.clone()
+    }
+
+    fn spawn(game: &mut TetrisGame, piece_type: TetrisPieceType) {
+        let config = &game.config;
+        game.active_piece = Some(TetrisPiece::new(piece_type, config.spawn_x(), config.spawn_y(), PINK));
+    }
+
+    #[test]
+    fn new_game_queues_three_pieces() {
+        let game = new_game();
+        assert!(game.active_piece.is_none());
+        assert_eq!(game.next_pieces.len(), 3);
+        assert_eq!(game.seed(), SEED);
+    }
+
+    #[test]
+    fn same_seed_deals_same_pieces() {
+        let mut a = new_game();
+        let mut b = new_game();
+        assert_eq!(a.next_pieces, b.next_pieces);
+        for _ in 0..20 {
+            assert_eq!(a.next_piece(), b.next_piece());
+            assert_eq!(a.next_color(), b.next_color());
         }
-        if can_rotate {
-            piece = TetrisPiece {
-                parts: new_parts,
-                color: piece.color,
-                piece_type: piece.piece_type,
-                rotation: piece.rotation
-            };
-        } else {
-            piece = TetrisPiece {
-                parts: piece.parts.clone(),
-                color: piece.color,
-                piece_type: piece.piece_type,
-                rotation: original_rotation
-            };
+    }
+
+    #[test]
+    fn start_spawns_the_first_piece() {
+        let mut game = new_game();
+        let first = game.next_pieces[0].clone();
+        assert_eq!(first.parts.iter().map(|p| p.y).min(), Some(game.config.spawn_y()));
+        game.start();
+        assert_eq!(game.active_piece, Some(first.shifted(0, 1)));
+        assert_eq!(game.next_pieces.len(), 3);
+        game.start();
+        assert_eq!(game.active_piece, Some(first.shifted(0, 1)));
+        assert_eq!(game.score, 0);
+    }
+
+    #[test]
+    fn piece_stops_at_floor_and_locks() {
+        let mut game = new_game();
+        let bottom = game.board.height() - 1;
+        spawn(&mut game, TetrisPieceType::Q);
+        for _ in 0..bottom {
+            game.try_move(0, 1);
         }
-        self.active_piece = self.pieces.len() as i64;
+        assert!(!game.board.is_occupied(3, bottom));
+        let lock_delay = game.config.lock_delay;
+        game.update(lock_delay);
+        assert!(game.board.is_occupied(3, bottom));
+        assert!(game.board.is_occupied(4, bottom - 1));
+        assert_eq!(game.state, GameState::Playing);
+    }
 
-        for part in &piece.parts {
-            let new_key = format!("{},{}", part.x, part.y);
-            self.grid.insert(new_key, self.active_piece.clone() as usize);
+    /// A game whose O piece rests on the floor of an empty board.
+    fn grounded(lock_reset: LockReset) -> TetrisGame {
+        let config = GameConfig { lock_reset, ..GameConfig::default() };
+        let mut game = TetrisGame::new(SEED, config);
+        spawn(&mut game, TetrisPieceType::Q);
+        while game.drop_distance() > 0 {
+            game.try_move(0, 1);
         }
-        self.pieces.push(piece);
+        game
     }
 
-    fn remove_line(&mut self, line_no: i64) {
-        for x in 0..H_CELLS {
-            let key = format!("{},{}", x, line_no);
-            if self.grid.contains_key(&key) {
-                if let Some(index) = self.grid.remove(&key) {
-                    self.pieces[index].hide_row(line_no);
-                }
-            }
+    fn floor_is_empty(game: &TetrisGame) -> bool {
+        let bottom = game.board.height() - 1;
+        (0..game.board.width()).all(|x| !game.board.is_occupied(x, bottom))
+    }
+
+    #[test]
+    fn infinite_lock_reset_never_locks_a_moving_piece() {
+        let mut game = grounded(LockReset::Infinite);
+        for i in 0..50 {
+            game.update(400.0);
+            let moved = if i % 2 == 0 { game.move_left() } else { game.move_right() };
+            assert!(moved);
         }
-        for y in (0..=line_no).rev() {
-            for x in 0..H_CELLS {
-                let key = format!("{},{}", x, y);
-                if self.grid.contains_key(&key) {
-                    if let Some(item) = self.grid.remove(&key) {
-                        let key = format!("{},{}", x, y+1);
-                        self.grid.insert(key, item);
-                        self.pieces[item].lower_row(y, 1);
-                    }
-                }
-            }
+        assert!(floor_is_empty(&game));
+        game.update(500.0);
+        assert!(!floor_is_empty(&game));
+    }
+
+    #[test]
+    fn limited_lock_reset_runs_out() {
+        let mut game = grounded(LockReset::Limited(15));
+        for i in 0..15 {
+            game.update(400.0);
+            let moved = if i % 2 == 0 { game.move_left() } else { game.move_right() };
+            assert!(moved);
         }
+        assert!(floor_is_empty(&game));
+        game.update(1.0);
+        assert!(!floor_is_empty(&game));
     }
 
-    pub(crate) fn check_lines(&mut self) -> i64 {
-        for y in (0..V_CELLS).rev() {
-            let mut all_filled = true;
-            for x in 0..H_CELLS {
-                let key = format!("{},{}", x, y);
-                all_filled = all_filled && self.grid.contains_key(&key);
-            }
-            if all_filled {
-                self.remove_line(y);
-                return y;
+    #[test]
+    fn step_lock_reset_ignores_moves() {
+        let mut game = grounded(LockReset::Step);
+        game.update(400.0);
+        game.move_left();
+        assert!(floor_is_empty(&game));
+        game.update(100.0);
+        assert!(!floor_is_empty(&game));
+    }
+
+    #[test]
+    fn falling_to_a_new_row_restarts_the_lock_delay() {
+        let config = GameConfig { lock_reset: LockReset::Step, ..GameConfig::default() };
+        let mut game = TetrisGame::new(SEED, config);
+        let bottom = game.board.height() - 1;
+        // a two row ledge on the right half for the piece to rest on
+        fill_row(&mut game, bottom, &[0, 1, 2, 3, 4]);
+        fill_row(&mut game, bottom - 1, &[0, 1, 2, 3, 4]);
+        spawn(&mut game, TetrisPieceType::Q);
+        game.move_right();
+        game.move_right();
+        while game.drop_distance() > 0 {
+            game.try_move(0, 1);
+        }
+        game.update(400.0);
+        game.move_left();
+        game.move_left();
+        game.try_move(0, 1);
+        game.update(400.0);
+        assert!(active_parts(&game).iter().any(|part| part.y == bottom - 1));
+    }
+
+    #[test]
+    fn hard_drop_locks_scores_and_spawns() {
+        let mut game = new_game();
+        let bottom = game.board.height() - 1;
+        spawn(&mut game, TetrisPieceType::Q);
+        let distance = game.drop_distance();
+        assert_eq!(distance, bottom - 1 - game.config.spawn_y());
+        let next = game.next_pieces[0].piece_type;
+        game.hard_drop();
+        assert_eq!(game.score, 2 * distance);
+        assert!(game.board.is_occupied(3, bottom));
+        assert!(game.board.is_occupied(4, bottom - 1));
+        assert_eq!(game.active_piece.as_ref().map(|p| p.piece_type), Some(next));
+    }
+
+    #[test]
+    fn ghost_piece_sits_on_the_stack() {
+        let mut game = new_game();
+        assert_eq!(game.ghost_piece(), None);
+        let bottom = game.board.height() - 1;
+        fill_row(&mut game, bottom, &[0]);
+        spawn(&mut game, TetrisPieceType::Q);
+        let ghost = game.ghost_piece().unwrap();
+        assert_eq!(ghost.parts.iter().map(|p| p.y).max(), Some(bottom - 1));
+        game.hard_drop();
+        assert!(game.board.is_occupied(3, bottom - 1));
+    }
+
+    #[test]
+    fn sonic_drop_lands_without_locking() {
+        let mut game = new_game();
+        spawn(&mut game, TetrisPieceType::Q);
+        let distance = game.drop_distance();
+        game.sonic_drop();
+        assert_eq!(game.drop_distance(), 0);
+        assert_eq!(game.score, distance);
+        assert!(game.active_piece.is_some());
+        game.move_left();
+        assert_eq!(active_parts(&game)[0].x, 2);
+    }
+
+    #[test]
+    fn soft_drop_scores_one_point_per_row() {
+        let mut game = new_game();
+        spawn(&mut game, TetrisPieceType::Q);
+        let start = active_parts(&game)[0].y;
+        // level 1 falls a row a second, 20 times faster while soft dropping
+        game.set_soft_drop(20.0);
+        game.update(110.0);
+        assert_eq!(active_parts(&game)[0].y, start + 2);
+        assert_eq!(game.score, 2);
+        game.set_soft_drop(1.0);
+        game.try_move(0, 1);
+        game.update(1000.0);
+        assert_eq!(game.score, 2);
+    }
+
+    #[test]
+    fn hold_takes_the_next_piece_then_swaps() {
+        let mut game = new_game();
+        game.start();
+        let first = game.active_piece.clone().unwrap();
+        let second = game.next_pieces[0].piece_type;
+        game.rotate_clockwise();
+        game.move_left();
+        game.hold();
+        let held = game.hold_piece.clone().unwrap();
+        assert_eq!(held, TetrisPiece::new(first.piece_type, 3, game.config.spawn_y(), first.color));
+        assert_eq!(game.active_piece.as_ref().map(|p| p.piece_type), Some(second));
+
+        // a second hold has to wait for the next piece
+        game.hold();
+        assert_eq!(game.active_piece.as_ref().map(|p| p.piece_type), Some(second));
+
+        game.hard_drop();
+        game.hold();
+        assert_eq!(game.active_piece, Some(held.shifted(0, 1)));
+        assert!(!game.can_hold);
+    }
+
+    #[test]
+    fn move_left_stops_at_wall() {
+        let mut game = new_game();
+        game.start();
+        for _ in 0..game.board.width() {
+            game.move_left();
+        }
+        let min_x = active_parts(&game).iter().map(|p| p.x).min();
+        assert_eq!(min_x, Some(0));
+    }
+
+    /// Fills `rows` completely except for `free` cells.
+    fn fill_except(game: &mut TetrisGame, rows: std::ops::Range<i64>, free: &[(i64, i64)]) {
+        for y in rows {
+            let skip: Vec<i64> = free.iter().filter(|cell| cell.1 == y).map(|cell| cell.0).collect();
+            fill_row(game, y, &skip);
+        }
+    }
+
+    fn cells(piece: &TetrisPiece) -> Vec<(i64, i64)> {
+        piece.parts.iter().map(|part| (part.x, part.y)).collect()
+    }
+
+    #[test]
+    fn o_piece_does_not_rotate() {
+        let mut game = new_game();
+        spawn(&mut game, TetrisPieceType::Q);
+        let before = game.active_piece.clone();
+        game.rotate_clockwise();
+        assert_eq!(game.active_piece, before);
+    }
+
+    #[test]
+    fn i_piece_kicks_off_the_left_wall() {
+        let mut game = new_game();
+        // vertical I in the left most column
+        let piece = TetrisPiece::new(TetrisPieceType::I, 0, 25, PINK).rotated(1).shifted(-2, 0);
+        assert_eq!(piece.parts.iter().map(|p| p.x).min(), Some(0));
+        game.active_piece = Some(piece);
+        game.rotate_clockwise();
+        let piece = game.active_piece.as_ref().unwrap();
+        assert_eq!(piece.rotation, 2);
+        let mut xs: Vec<i64> = piece.parts.iter().map(|p| p.x).collect();
+        xs.sort();
+        assert_eq!(xs, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn t_piece_uses_the_last_kick_into_a_tst_slot() {
+        let mut game = new_game();
+        let t = TetrisPiece::new(TetrisPieceType::T, 4, 25, PINK);
+        let target = t.rotated(1).shifted(-1, 2);
+        let mut free = cells(&t);
+        free.extend(cells(&target));
+        fill_except(&mut game, 24..30, &free);
+        game.active_piece = Some(t);
+        game.rotate_clockwise();
+        assert_eq!(game.active_piece, Some(target));
+    }
+
+    #[test]
+    fn counterclockwise_and_half_turns() {
+        let config = GameConfig { half_turns: true, ..GameConfig::default() };
+        let mut game = TetrisGame::new(SEED, config);
+        spawn(&mut game, TetrisPieceType::T);
+        game.try_move(0, 1);
+        game.rotate_counterclockwise();
+        assert_eq!(game.active_piece.as_ref().map(|p| p.rotation), Some(3));
+        game.rotate_180();
+        assert_eq!(game.active_piece.as_ref().map(|p| p.rotation), Some(1));
+
+        let mut game = new_game();
+        spawn(&mut game, TetrisPieceType::T);
+        game.rotate_180();
+        assert_eq!(game.active_piece.as_ref().map(|p| p.rotation), Some(0));
+    }
+
+    #[test]
+    fn rotation_fails_when_no_kick_fits() {
+        let mut game = new_game();
+        let t = TetrisPiece::new(TetrisPieceType::T, 4, 25, PINK);
+        fill_except(&mut game, 20..30, &cells(&t));
+        game.active_piece = Some(t.clone());
+        game.rotate_clockwise();
+        game.rotate_counterclockwise();
+        assert_eq!(game.active_piece, Some(t));
+    }
+
+    #[test]
+    fn spawning_into_the_stack_is_a_block_out() {
+        let mut game = new_game();
+        let spawn_y = game.config.spawn_y();
+        fill_row(&mut game, spawn_y, &[0]);
+        fill_row(&mut game, spawn_y + 1, &[0]);
+        game.start();
+        assert_eq!(game.state, GameState::GameOver(TopOut::BlockOut));
+        assert!(game.active_piece.is_none());
+        let score = game.score;
+        game.start();
+        assert!(game.active_piece.is_none());
+        assert_eq!(game.score, score);
+    }
+
+    #[test]
+    fn locking_inside_the_hidden_rows_is_a_lock_out() {
+        let mut game = new_game();
+        let hidden_rows = game.config.hidden_rows;
+        fill_row(&mut game, hidden_rows, &[0]);
+        spawn(&mut game, TetrisPieceType::Q);
+        let lock_delay = game.config.lock_delay;
+        game.update(lock_delay);
+        assert!(game.active_piece.is_none());
+        assert_eq!(game.state, GameState::GameOver(TopOut::LockOut));
+    }
+
+    #[test]
+    fn partial_lock_out_is_optional() {
+        for partial_lock_out in &[false, true] {
+            let config = GameConfig { partial_lock_out: *partial_lock_out, ..GameConfig::default() };
+            let mut game = TetrisGame::new(SEED, config);
+            let hidden_rows = game.config.hidden_rows;
+            fill_row(&mut game, hidden_rows + 1, &[0]);
+            spawn(&mut game, TetrisPieceType::Q);
+            game.try_move(0, 1);
+            let lock_delay = game.config.lock_delay;
+            game.update(lock_delay);
+            if *partial_lock_out {
+                assert_eq!(game.state, GameState::GameOver(TopOut::PartialLockOut));
+            } else {
+                // the piece locks, leaving the next one no room to spawn
+                assert_eq!(game.state, GameState::GameOver(TopOut::BlockOut));
             }
         }
-        return -1;
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn narrow_tall_board_clears_lines() {
+        let config = GameConfig { width: 4, visible_height: 40, hidden_rows: 0, ..GameConfig::default() };
+        let mut game = TetrisGame::new(SEED, config);
+        fill_row(&mut game, 39, &[3]);
+        spawn(&mut game, TetrisPieceType::I);
+        game.move_right();
+        assert_eq!(active_parts(&game)[0].x, 0);
+        for _ in 0..40 {
+            game.try_move(0, 1);
+        }
+        let lock_delay = game.config.lock_delay;
+        game.update(lock_delay);
+        assert_eq!(game.score, 100);
+        assert!(game.board.is_occupied(0, 39));
+        assert!(!game.board.is_occupied(3, 39));
+        assert!(!game.board.is_occupied(0, 38));
+    }
+
+    #[test]
+    fn clearing_a_line_scores_and_shifts_rows() {
+        let mut game = new_game();
+        let bottom = game.board.height() - 1;
+        fill_row(&mut game, bottom - 1, &[0]);
+        complete_rows(&mut game, 1);
+        assert_eq!(game.score, 100);
+        assert_eq!(game.cleared_rows, vec![bottom]);
+        assert!(game.clearing > 0);
+        assert!(!game.board.is_occupied(0, bottom));
+        assert!(game.board.is_occupied(1, bottom));
+    }
+
+    #[test]
+    fn clearing_lines_levels_up_and_raises_the_multiplier() {
+        let config = GameConfig { lines_per_level: 2, ..GameConfig::default() };
+        let mut game = TetrisGame::new(SEED, config);
+        leave_a_cell(&mut game);
+        let bottom = game.board.height() - 1;
+        clear_filled(&mut game, 2);
+        assert_eq!((game.lines, game.level, game.score), (2, 2, 300));
+        for y in bottom - 3..=bottom {
+            fill_row(&mut game, y, &[]);
+        }
+        game.hard_drop();
+        // the tetris is scored at the level it was made on
+        assert_eq!(game.lines, 6);
+        assert_eq!(game.level, 4);
+        assert!(game.score >= 300 + 800 * 2);
+    }
+
+    #[test]
+    fn gravity_moves_the_piece_between_ticks() {
+        let mut game = new_game();
+        spawn(&mut game, TetrisPieceType::Q);
+        let start = active_parts(&game)[0].y;
+        // level 1 falls a row a second
+        game.update(500.0);
+        assert_eq!(active_parts(&game)[0].y, start);
+        game.update(600.0);
+        assert_eq!(active_parts(&game)[0].y, start + 1);
+    }
+
+    #[test]
+    fn twenty_g_drops_to_the_stack_at_once() {
+        let config = GameConfig { gravity: GravityCurve::Custom(vec![20.0]), ..GameConfig::default() };
+        let mut game = TetrisGame::new(SEED, config);
+        spawn(&mut game, TetrisPieceType::Q);
+        game.update(1.0);
+        assert_eq!(game.drop_distance(), 0);
+        assert!(game.active_piece.is_some());
+    }
+
+    /// Leaves a single cell well above the rows tests clear, so clearing
+    /// them is no perfect clear.
+    fn leave_a_cell(game: &mut TetrisGame) {
+        fill_row(game, 24, &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    fn finish_clearing(game: &mut TetrisGame) {
+        while game.clearing > 0 {
+            game.step_clearing();
+        }
+    }
+
+    #[test]
+    fn next_piece_waits_for_the_line_clear() {
+        let mut game = new_game();
+        complete_rows(&mut game, 1);
+        assert!(game.clearing > 0);
+        assert!(game.active_piece.is_none());
+        finish_clearing(&mut game);
+        assert!(game.active_piece.is_some());
+    }
+
+    #[test]
+    fn entry_delay_holds_back_the_next_piece() {
+        let config = GameConfig { entry_delay: 100.0, ..GameConfig::default() };
+        let mut game = TetrisGame::new(SEED, config);
+        spawn(&mut game, TetrisPieceType::Q);
+        game.hard_drop();
+        assert!(game.active_piece.is_none());
+        game.update(60.0);
+        assert!(game.active_piece.is_none());
+        game.update(40.0);
+        assert!(game.active_piece.is_some());
+    }
+
+    #[test]
+    fn rotation_held_through_the_clear_turns_the_next_spawn() {
+        let mut game = new_game();
+        complete_rows(&mut game, 1);
+        let next = game.next_pieces[0].clone();
+        // a press between pieces does nothing by itself, the held key does
+        game.rotate_clockwise();
+        game.set_initial_actions(3, false);
+        finish_clearing(&mut game);
+        let piece = game.active_piece.clone().unwrap();
+        assert_eq!(piece.piece_type, next.piece_type);
+        assert_eq!(piece.rotation, 3);
+        // held through a lock with no clear, the next piece enters at once
+        game.set_initial_actions(1, true);
+        let next = game.next_pieces[0].clone();
+        game.hard_drop();
+        let piece = game.active_piece.clone().unwrap();
+        assert_eq!((piece.piece_type, piece.rotation), (next.piece_type, 0));
+        assert!(game.can_hold);
+    }
+
+    #[test]
+    fn half_turn_is_only_initial_with_half_turns_enabled() {
+        let mut game = new_game();
+        game.set_initial_actions(2, false);
+        game.start();
+        assert_eq!(game.active_piece.as_ref().map(|piece| piece.rotation), Some(0));
+    }
+
+    #[test]
+    fn hold_held_through_the_clear_holds_the_next_spawn() {
+        let mut game = new_game();
+        complete_rows(&mut game, 1);
+        let next = game.next_pieces[0].piece_type;
+        let after = game.next_pieces[1].piece_type;
+        game.set_initial_actions(0, true);
+        finish_clearing(&mut game);
+        assert_eq!(game.hold_piece.as_ref().map(|piece| piece.piece_type), Some(next));
+        assert_eq!(game.active_piece.as_ref().map(|piece| piece.piece_type), Some(after));
+        assert!(!game.can_hold);
+    }
+
+    /// A T slot on the floor with an overhang over its left corner.
+    fn t_slot(game: &mut TetrisGame) -> i64 {
+        let bottom = game.board.height() - 1;
+        fill_row(game, bottom, &[4]);
+        fill_row(game, bottom - 1, &[3, 4, 5]);
+        let overhang: Vec<i64> = (0..10).filter(|x| *x != 3).collect();
+        fill_row(game, bottom - 2, &overhang);
+        bottom
+    }
+
+    #[test]
+    fn rotating_into_a_slot_is_a_t_spin_double() {
+        let mut game = new_game();
+        let bottom = t_slot(&mut game);
+        game.active_piece = Some(TetrisPiece::new(TetrisPieceType::T, 3, bottom - 2, PINK).rotated(1));
+        game.rotate_clockwise();
+        game.hard_drop();
+        assert_eq!(game.last_action, Some(ClearAction { lines: 2, t_spin: Some(TSpin::Full), back_to_back: false, perfect_clear: false }));
+        assert_eq!(game.score, 1200);
+    }
+
+    #[test]
+    fn dropping_into_a_slot_is_not_a_t_spin() {
+        let mut game = new_game();
+        leave_a_cell(&mut game);
+        let bottom = game.board.height() - 1;
+        fill_row(&mut game, bottom, &[4]);
+        fill_row(&mut game, bottom - 1, &[3, 4, 5]);
+        game.active_piece = Some(TetrisPiece::new(TetrisPieceType::T, 3, bottom - 3, PINK).rotated(2));
+        game.rotate_clockwise();
+        game.rotate_counterclockwise();
+        // falling after the rotation cancels the spin
+        game.hard_drop();
+        assert_eq!(game.last_action, Some(ClearAction { lines: 2, t_spin: None, back_to_back: false, perfect_clear: false }));
+        assert_eq!(game.score, 2 + 300);
+    }
+
+    #[test]
+    fn tst_kick_makes_a_full_t_spin_triple() {
+        let mut game = new_game();
+        let t = TetrisPiece::new(TetrisPieceType::T, 4, 25, PINK);
+        let mut free = cells(&t);
+        free.extend(cells(&t.rotated(1).shifted(-1, 2)));
+        // keep the roof from clearing with the slot
+        free.push((0, 24));
+        fill_except(&mut game, 24..30, &free);
+        game.active_piece = Some(t);
+        game.rotate_clockwise();
+        game.hard_drop();
+        assert_eq!(game.last_action, Some(ClearAction { lines: 3, t_spin: Some(TSpin::Full), back_to_back: false, perfect_clear: false }));
+    }
+
+    /// Fills the bottom `rows` rows but for their first column and locks a
+    /// piece into it, the way play completes them. With no rows the piece
+    /// locks on the floor without clearing anything.
+    fn complete_rows(game: &mut TetrisGame, rows: i64) {
+        let bottom = game.board.height() - 1;
+        let top = bottom + 1 - rows.max(1);
+        for y in bottom + 1 - rows..=bottom {
+            fill_row(game, y, &[0]);
+        }
+        let column = TetrisPiece {
+            parts: (top..=bottom).map(|y| TetrisPart::new(0, y)).collect(),
+            color: PINK,
+            piece_type: TetrisPieceType::I,
+            rotation: 0,
+            x: 0,
+            y: top
+        };
+        game.active_piece = None;
+        game.lock_and_spawn(column);
+    }
+
+    fn clear_filled(game: &mut TetrisGame, rows: i64) {
+        complete_rows(game, rows);
+        finish_clearing(game);
+    }
+
+    #[test]
+    fn back_to_back_and_combo_bonuses() {
+        let mut game = new_game();
+        leave_a_cell(&mut game);
+        clear_filled(&mut game, 4);
+        assert_eq!((game.score, game.combo, game.back_to_back), (800, Some(0), true));
+        clear_filled(&mut game, 4);
+        assert!(game.last_action.unwrap().back_to_back);
+        assert_eq!((game.score, game.combo), (800 + 1200 + 50, Some(1)));
+        clear_filled(&mut game, 1);
+        assert_eq!((game.score, game.combo, game.back_to_back), (2050 + 100 + 100, Some(2), false));
+        clear_filled(&mut game, 0);
+        assert_eq!((game.score, game.combo), (2250, None));
+    }
+
+    #[test]
+    fn clearing_every_row_is_a_perfect_clear() {
+        let mut game = new_game();
+        clear_filled(&mut game, 2);
+        assert!(game.last_action.unwrap().perfect_clear);
+        assert_eq!(game.score, 300 + 1200);
+        assert_eq!(game.perfect_clears, 1);
+        // a leftover cell above the cleared row spoils the next one
+        let bottom = game.board.height() - 1;
+        fill_row(&mut game, bottom - 1, &[0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        clear_filled(&mut game, 1);
+        assert!(!game.last_action.unwrap().perfect_clear);
+        assert_eq!(game.perfect_clears, 1);
+    }
+
+    #[test]
+    fn paused_game_ignores_input_and_time() {
+        let mut game = new_game();
+        game.start();
+        let piece = game.active_piece.clone();
+        game.toggle_pause();
+        assert_eq!(game.state, GameState::Paused);
+        game.move_left();
+        game.rotate_clockwise();
+        game.hard_drop();
+        game.hold();
+        game.update(10_000.0);
+        assert_eq!(game.active_piece, piece);
+        assert_eq!(game.score, 0);
+        game.toggle_pause();
+        assert_eq!(game.state, GameState::Playing);
+        game.hard_drop();
+        assert!(game.score > 0);
+    }
+
+    #[test]
+    fn game_over_can_not_be_paused() {
+        let mut game = new_game();
+        game.state = GameState::GameOver(TopOut::BlockOut);
+        game.pause();
+        assert_eq!(game.state, GameState::GameOver(TopOut::BlockOut));
+    }
+
+    #[test]
+    fn steps_run_the_clear_animation_then_gravity() {
+        let mut game = new_game();
+        complete_rows(&mut game, 1);
+        let ticks = (CLEARING + CLEARING_STEP - 1) / CLEARING_STEP;
+        for _ in 0..ticks {
+            assert!(game.active_piece.is_none());
+            game.step();
+        }
+        let start = active_parts(&game)[0].y;
+        // level 1 gravity is one row every 60 ticks
+        for _ in 0..61 {
+            game.step();
+        }
+        assert_eq!(active_parts(&game)[0].y, start + 1);
+    }
+}
@@ -0,0 +1,132 @@
+/// How far a stick has to be pushed to count as pressed, and how far it has
+/// to come back before it counts as released, so a stick resting near the
+/// threshold does not chatter.
+const STICK_PRESS: f64 = 0.5;
+const STICK_RELEASE: f64 = 0.3;
+
+/// Names of the buttons in the browser's standard gamepad mapping.
+const BUTTON_LABELS: [&str; 17] = [
+    "Pad A", "Pad B", "Pad X", "Pad Y", "Pad LB", "Pad RB", "Pad LT", "Pad RT",
+    "Pad Back", "Pad Start", "Pad L3", "Pad R3",
+    "Pad Up", "Pad Down", "Pad Left", "Pad Right", "Pad Home"
+];
+
+/// Binding code for a button, e.g. `Gamepad0` for the bottom face button.
+pub(crate) fn button_code(index: usize) -> String {
+    format!("Gamepad{}", index)
+}
+
+/// Binding code for pushing a stick axis one way, e.g. `GamepadAxis0-` for
+/// the left stick pushed left.
+pub(crate) fn axis_code(index: usize, positive: bool) -> String {
+    format!("GamepadAxis{}{}", index, if positive { '+' } else { '-' })
+}
+
+/// Label for a gamepad binding code, `None` for keyboard codes.
+pub(crate) fn control_label(code: &str) -> Option<&'static str> {
+    if let Some(axis) = code.strip_prefix("GamepadAxis") {
+        return Some(match axis {
+            "0-" => "Stick left",
+            "0+" => "Stick right",
+            "1-" => "Stick up",
+            "1+" => "Stick down",
+            _ => "Pad stick"
+        });
+    }
+    let index: usize = code.strip_prefix("Gamepad")?.parse().ok()?;
+    Some(BUTTON_LABELS.get(index).copied().unwrap_or("Pad button"))
+}
+
+/// What one gamepad reports in a frame.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub(crate) struct PadReading {
+    pub(crate) buttons: Vec<bool>,
+    pub(crate) axes: Vec<f64>
+}
+
+/// Turns gamepad readings, which are polled every frame, into presses and
+/// releases like keyboard events.
+pub(crate) struct GamepadPoller {
+    held: Vec<String>
+}
+
+impl GamepadPoller {
+
+    pub(crate) fn new() -> GamepadPoller {
+        GamepadPoller {
+            held: Vec::new()
+        }
+    }
+
+    /// Takes this frame's readings of every connected pad and returns the
+    /// codes pressed and released since the last poll.
+    pub(crate) fn poll(&mut self, pads: &[PadReading]) -> (Vec<String>, Vec<String>) {
+        let mut active: Vec<String> = Vec::new();
+        for pad in pads {
+            for (index, pressed) in pad.buttons.iter().enumerate() {
+                let code = button_code(index);
+                if *pressed && !active.contains(&code) {
+                    active.push(code);
+                }
+            }
+            for (index, value) in pad.axes.iter().enumerate() {
+                for positive in [false, true] {
+                    let code = axis_code(index, positive);
+                    let pushed = if positive { *value } else { -*value };
+                    let threshold = if self.held.contains(&code) { STICK_RELEASE } else { STICK_PRESS };
+                    if pushed > threshold && !active.contains(&code) {
+                        active.push(code);
+                    }
+                }
+            }
+        }
+        let pressed = active.iter().filter(|code| !self.held.contains(code)).cloned().collect();
+        let released = self.held.iter().filter(|code| !active.contains(code)).cloned().collect();
+        self.held = active;
+        (pressed, released)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buttons(pressed: &[usize]) -> PadReading {
+        PadReading {
+            buttons: (0..17).map(|index| pressed.contains(&index)).collect(),
+            axes: vec![0.0; 4]
+        }
+    }
+
+    fn stick(x: f64, y: f64) -> PadReading {
+        PadReading { axes: vec![x, y, 0.0, 0.0], ..buttons(&[]) }
+    }
+
+    #[test]
+    fn buttons_are_pressed_once_and_released_once() {
+        let mut poller = GamepadPoller::new();
+        assert_eq!(poller.poll(&[buttons(&[0, 14])]), (vec![button_code(0), button_code(14)], vec![]));
+        assert_eq!(poller.poll(&[buttons(&[0, 14])]), (vec![], vec![]));
+        assert_eq!(poller.poll(&[buttons(&[14])]), (vec![], vec![button_code(0)]));
+        // an unplugged pad releases everything it held
+        assert_eq!(poller.poll(&[]), (vec![], vec![button_code(14)]));
+    }
+
+    #[test]
+    fn sticks_press_past_one_threshold_and_release_below_another() {
+        let mut poller = GamepadPoller::new();
+        assert_eq!(poller.poll(&[stick(-0.4, 0.0)]), (vec![], vec![]));
+        assert_eq!(poller.poll(&[stick(-0.6, 0.0)]), (vec![axis_code(0, false)], vec![]));
+        assert_eq!(poller.poll(&[stick(-0.4, 0.0)]), (vec![], vec![]));
+        assert_eq!(poller.poll(&[stick(-0.2, 0.9)]), (vec![axis_code(1, true)], vec![axis_code(0, false)]));
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(control_label("Gamepad0"), Some("Pad A"));
+        assert_eq!(control_label("Gamepad14"), Some("Pad Left"));
+        assert_eq!(control_label("GamepadAxis1+"), Some("Stick down"));
+        assert_eq!(control_label("KeyX"), None);
+    }
+}
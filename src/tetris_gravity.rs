@@ -0,0 +1,106 @@
+/// Fastest possible gravity: the piece falls to the stack within one frame.
+pub(crate) const MAX_GRAVITY: f64 = 20.0;
+
+/// Frames per second gravity is measured in.
+pub(crate) const FRAME_RATE: f64 = 60.0;
+
+/// NTSC NES frames per row for levels 0 to 29, level 29 and up is 1.
+const NES_FRAMES_PER_ROW: [f64; 30] = [
+    48.0, 43.0, 38.0, 33.0, 28.0, 23.0, 18.0, 13.0, 8.0, 6.0,
+    5.0, 5.0, 5.0, 4.0, 4.0, 4.0, 3.0, 3.0, 3.0, 2.0,
+    2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 1.0
+];
+
+/// How fast pieces fall at each level, in rows per frame (G). Levels start
+/// at 1.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum GravityCurve {
+    /// The guideline formula `(0.8 - (level - 1) * 0.007) ^ (level - 1)`
+    /// seconds per row.
+    Guideline,
+    /// The NES speeds, with level 1 playing like NES level 0.
+    Nes,
+    /// Gravity for level 1, 2 and so on, the last entry holds for every
+    /// level after it.
+    Custom(Vec<f64>)
+}
+
+impl GravityCurve {
+
+    pub(crate) fn from_name(name: &str) -> Option<GravityCurve> {
+        match name {
+            "guideline" => Some(GravityCurve::Guideline),
+            "nes" => Some(GravityCurve::Nes),
+            _ => {
+                // a comma separated list of G values, e.g. `0.02,0.05,1,20`
+                let table: Result<Vec<f64>, _> = name.split(',').map(|value| value.parse()).collect();
+                table.ok().filter(|table| !table.is_empty()).map(GravityCurve::Custom)
+            }
+        }
+    }
+
+    /// Rows per frame at `level`, never more than `MAX_GRAVITY`.
+    pub(crate) fn gravity(&self, level: usize) -> f64 {
+        let level = level.max(1);
+        let gravity = match self {
+            GravityCurve::Guideline => {
+                // the formula reaches 20G by level 20 and breaks down past level 100
+                let steps = (level.min(20) - 1) as f64;
+                let seconds_per_row = (0.8 - steps * 0.007).powf(steps);
+                1.0 / (seconds_per_row * FRAME_RATE)
+            }
+            GravityCurve::Nes => {
+                let index = (level - 1).min(NES_FRAMES_PER_ROW.len() - 1);
+                1.0 / NES_FRAMES_PER_ROW[index]
+            }
+            GravityCurve::Custom(table) => {
+                match table.get(level - 1).or_else(|| table.last()) {
+                    Some(gravity) => *gravity,
+                    None => 0.0
+                }
+            }
+        };
+        gravity.min(MAX_GRAVITY)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guideline_starts_at_one_row_a_second() {
+        assert!((GravityCurve::Guideline.gravity(1) - 1.0 / 60.0).abs() < 1e-9);
+        // level 15 falls about 2.36 rows per frame
+        let level_15 = GravityCurve::Guideline.gravity(15);
+        assert!(level_15 > 2.3 && level_15 < 2.4, "{}", level_15);
+        assert_eq!(GravityCurve::Guideline.gravity(200), MAX_GRAVITY);
+    }
+
+    #[test]
+    fn gravity_never_slows_down() {
+        for curve in &[GravityCurve::Guideline, GravityCurve::Nes] {
+            for level in 1..40 {
+                assert!(curve.gravity(level + 1) >= curve.gravity(level));
+            }
+        }
+    }
+
+    #[test]
+    fn nes_uses_the_frame_table() {
+        assert_eq!(GravityCurve::Nes.gravity(1), 1.0 / 48.0);
+        assert_eq!(GravityCurve::Nes.gravity(20), 1.0 / 2.0);
+        assert_eq!(GravityCurve::Nes.gravity(35), 1.0);
+    }
+
+    #[test]
+    fn custom_tables_repeat_their_last_entry() {
+        let curve = GravityCurve::from_name("0.5,2,50").unwrap();
+        assert_eq!(curve, GravityCurve::Custom(vec![0.5, 2.0, 50.0]));
+        assert_eq!(curve.gravity(1), 0.5);
+        assert_eq!(curve.gravity(2), 2.0);
+        assert_eq!(curve.gravity(9), MAX_GRAVITY);
+        assert_eq!(GravityCurve::from_name("fast"), None);
+    }
+}
@@ -0,0 +1,207 @@
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Direction {
+    Left,
+    Right
+}
+
+/// Player tuned timings for held keys.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct Handling {
+    /// Delayed auto shift, milliseconds a direction is held before it repeats.
+    pub(crate) das: f64,
+    /// Auto repeat rate, milliseconds between repeated moves. 0 moves all the
+    /// way to the wall at once.
+    pub(crate) arr: f64,
+    /// Soft drop falls this many times faster than gravity.
+    pub(crate) soft_drop_factor: f64
+}
+
+impl Default for Handling {
+    fn default() -> Handling {
+        Handling {
+            das: 167.0,
+            arr: 33.0,
+            soft_drop_factor: 20.0
+        }
+    }
+}
+
+impl Handling {
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.das < 0.0 || self.arr < 0.0 {
+            return Err(format!("das and arr can not be negative, got {} and {}", self.das, self.arr));
+        }
+        if self.soft_drop_factor < 1.0 {
+            return Err(format!("soft drop factor must be at least 1, got {}", self.soft_drop_factor));
+        }
+        Ok(())
+    }
+
+    /// Moves made by a direction held for `held` milliseconds, the first
+    /// press not included.
+    fn repeats(&self, held: f64) -> usize {
+        if held < self.das {
+            0
+        } else if self.arr == 0.0 {
+            usize::MAX
+        } else {
+            ((held - self.das) / self.arr) as usize + 1
+        }
+    }
+}
+
+/// Turns left and right key presses into moves. The charge lives outside the
+/// game so a direction held through a lock shifts the next piece right away.
+pub(crate) struct AutoShift {
+    left: bool,
+    right: bool,
+    /// Most recently pressed direction that is still held.
+    direction: Option<Direction>,
+    /// A fresh press waiting to make its single move.
+    pending: bool,
+    /// Milliseconds `direction` has been held.
+    charge: f64
+}
+
+impl AutoShift {
+
+    pub(crate) fn new() -> AutoShift {
+        AutoShift {
+            left: false,
+            right: false,
+            direction: None,
+            pending: false,
+            charge: 0.0
+        }
+    }
+
+    fn held(&mut self, direction: Direction) -> &mut bool {
+        match direction {
+            Direction::Left => &mut self.left,
+            Direction::Right => &mut self.right
+        }
+    }
+
+    pub(crate) fn press(&mut self, direction: Direction) {
+        if *self.held(direction) {
+            // keyboard auto repeat, the charge already takes care of it
+            return;
+        }
+        *self.held(direction) = true;
+        self.direction = Some(direction);
+        self.pending = true;
+        self.charge = 0.0;
+    }
+
+    /// Releasing the active direction hands over to the other one when it is
+    /// still held, which charges again from zero.
+    pub(crate) fn release(&mut self, direction: Direction) {
+        *self.held(direction) = false;
+        if self.direction == Some(direction) {
+            let other = match direction {
+                Direction::Left => Direction::Right,
+                Direction::Right => Direction::Left
+            };
+            self.direction = if *self.held(other) { Some(other) } else { None };
+            self.pending = false;
+            self.charge = 0.0;
+        }
+    }
+
+    /// Advances the charge by `elapsed` milliseconds and returns the direction
+    /// to move in with the number of cells, `usize::MAX` meaning to the wall.
+    pub(crate) fn update(&mut self, elapsed: f64, handling: &Handling) -> Option<(Direction, usize)> {
+        let direction = self.direction?;
+        let steps = if self.pending {
+            // the press itself moves once, holding starts counting from here
+            self.pending = false;
+            1
+        } else {
+            let before = handling.repeats(self.charge);
+            self.charge += elapsed;
+            if handling.arr == 0.0 && self.charge >= handling.das {
+                // keeps pushing every update, so new pieces and pieces
+                // freed from the stack also go to the wall
+                usize::MAX
+            } else {
+                handling.repeats(self.charge) - before
+            }
+        };
+        if steps == 0 {
+            None
+        } else {
+            Some((direction, steps))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handling(das: f64, arr: f64) -> Handling {
+        Handling { das, arr, ..Handling::default() }
+    }
+
+    #[test]
+    fn press_moves_once_then_waits_for_das() {
+        let handling = handling(100.0, 20.0);
+        let mut shift = AutoShift::new();
+        shift.press(Direction::Left);
+        assert_eq!(shift.update(16.0, &handling), Some((Direction::Left, 1)));
+        assert_eq!(shift.update(90.0, &handling), None);
+        assert_eq!(shift.update(10.0, &handling), Some((Direction::Left, 1)));
+        assert_eq!(shift.update(19.0, &handling), None);
+        assert_eq!(shift.update(1.0, &handling), Some((Direction::Left, 1)));
+        // a long frame catches up on every repeat it covered
+        assert_eq!(shift.update(60.0, &handling), Some((Direction::Left, 3)));
+    }
+
+    #[test]
+    fn zero_arr_moves_to_the_wall() {
+        let handling = handling(100.0, 0.0);
+        let mut shift = AutoShift::new();
+        shift.press(Direction::Right);
+        shift.update(16.0, &handling);
+        assert_eq!(shift.update(100.0, &handling), Some((Direction::Right, usize::MAX)));
+        assert_eq!(shift.update(16.0, &handling), Some((Direction::Right, usize::MAX)));
+        shift.release(Direction::Right);
+        assert_eq!(shift.update(16.0, &handling), None);
+    }
+
+    #[test]
+    fn key_repeat_does_not_restart_the_charge() {
+        let handling = handling(100.0, 20.0);
+        let mut shift = AutoShift::new();
+        shift.press(Direction::Left);
+        shift.update(16.0, &handling);
+        shift.update(80.0, &handling);
+        shift.press(Direction::Left);
+        assert_eq!(shift.update(20.0, &handling), Some((Direction::Left, 1)));
+    }
+
+    #[test]
+    fn last_pressed_direction_wins() {
+        let handling = handling(100.0, 20.0);
+        let mut shift = AutoShift::new();
+        shift.press(Direction::Left);
+        shift.update(150.0, &handling);
+        shift.press(Direction::Right);
+        assert_eq!(shift.update(16.0, &handling), Some((Direction::Right, 1)));
+        shift.release(Direction::Right);
+        // back to left, charging from zero without another tap
+        assert_eq!(shift.update(50.0, &handling), None);
+        assert_eq!(shift.update(50.0, &handling), Some((Direction::Left, 1)));
+        shift.release(Direction::Left);
+        assert_eq!(shift.update(500.0, &handling), None);
+    }
+
+    #[test]
+    fn rejects_negative_timings() {
+        assert_eq!(Handling::default().validate(), Ok(()));
+        assert!(handling(-1.0, 0.0).validate().is_err());
+        assert!(Handling { soft_drop_factor: 0.5, ..Handling::default() }.validate().is_err());
+    }
+}
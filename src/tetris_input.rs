@@ -0,0 +1,397 @@
+use std::collections::VecDeque;
+use crate::tetris_clock::TICK_MS;
+use crate::tetris_game::TetrisGame;
+use crate::tetris_handling::{AutoShift, Direction, Handling};
+
+/// Everything a player can ask for, whatever the input device.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub(crate) enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    SonicDrop,
+    RotateClockwise,
+    RotateCounterclockwise,
+    Rotate180,
+    Hold,
+    Pause,
+    Restart
+}
+
+impl Action {
+
+    /// Every action, in the order the rebinding screen lists them.
+    pub(crate) const ALL: [Action; 11] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::SonicDrop,
+        Action::RotateClockwise,
+        Action::RotateCounterclockwise,
+        Action::Rotate180,
+        Action::Hold,
+        Action::Pause,
+        Action::Restart
+    ];
+
+    /// Reads the name `{:?}` prints, e.g. `MoveLeft`.
+    pub(crate) fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|action| format!("{:?}", action) == name)
+    }
+
+    /// Name shown to the player.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::SoftDrop => "Soft drop",
+            Action::HardDrop => "Hard drop",
+            Action::SonicDrop => "Sonic drop",
+            Action::RotateClockwise => "Rotate right",
+            Action::RotateCounterclockwise => "Rotate left",
+            Action::Rotate180 => "Rotate 180",
+            Action::Hold => "Hold",
+            Action::Pause => "Pause",
+            Action::Restart => "Restart"
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum InputKind {
+    Press,
+    Release
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct InputEvent {
+    pub(crate) action: Action,
+    pub(crate) kind: InputKind,
+    /// Milliseconds since the page started, on the same clock as the ticks.
+    pub(crate) time: f64
+}
+
+/// Input events waiting for the simulation tick they happened before.
+pub(crate) struct InputQueue {
+    events: VecDeque<InputEvent>
+}
+
+impl InputQueue {
+
+    pub(crate) fn new() -> InputQueue {
+        InputQueue {
+            events: VecDeque::new()
+        }
+    }
+
+    /// Adds an event in time order. Gamepads are polled once a frame, so
+    /// their events can arrive after later key presses.
+    pub(crate) fn push(&mut self, action: Action, kind: InputKind, time: f64) {
+        let index = self.events.iter().rposition(|event| event.time <= time).map_or(0, |index| index + 1);
+        self.events.insert(index, InputEvent { action, kind, time });
+    }
+
+    /// Removes and returns every event up to `time`, oldest first.
+    pub(crate) fn drain_until(&mut self, time: f64) -> Vec<InputEvent> {
+        let count = self.events.iter().take_while(|event| event.time <= time).count();
+        self.events.drain(..count).collect()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+/// Applies input events to a game and runs its ticks, keeping track of the
+/// held directions, soft drop, rotations and hold in between.
+pub(crate) struct Controller {
+    handling: Handling,
+    auto_shift: AutoShift,
+    soft_drop: bool,
+    /// Rotation actions held down, the most recent last.
+    rotations: Vec<Action>,
+    holding: bool
+}
+
+impl Controller {
+
+    pub(crate) fn new(handling: Handling) -> Controller {
+        Controller {
+            handling,
+            auto_shift: AutoShift::new(),
+            soft_drop: false,
+            rotations: Vec::new(),
+            holding: false
+        }
+    }
+
+    /// Applies one event. `Restart` is left to the frontend, which owns the
+    /// game.
+    pub(crate) fn apply(&mut self, game: &mut TetrisGame, event: &InputEvent) {
+        self.track_held(event);
+        game.set_initial_actions(self.initial_turns(), self.holding);
+        match (event.action, event.kind) {
+            (Action::MoveLeft, InputKind::Press) => {
+                self.auto_shift.press(Direction::Left);
+                // the tap moves right away so it keeps its place among the
+                // other inputs of the tick
+                self.shift(game, 0.0);
+            }
+            (Action::MoveRight, InputKind::Press) => {
+                self.auto_shift.press(Direction::Right);
+                self.shift(game, 0.0);
+            }
+            (Action::MoveLeft, InputKind::Release) => self.auto_shift.release(Direction::Left),
+            (Action::MoveRight, InputKind::Release) => self.auto_shift.release(Direction::Right),
+            (Action::SoftDrop, kind) => self.soft_drop = kind == InputKind::Press,
+            (_, InputKind::Release) => {}
+            (Action::HardDrop, _) => game.hard_drop(),
+            (Action::SonicDrop, _) => game.sonic_drop(),
+            (Action::RotateClockwise, _) => game.rotate_clockwise(),
+            (Action::RotateCounterclockwise, _) => game.rotate_counterclockwise(),
+            (Action::Rotate180, _) => game.rotate_180(),
+            (Action::Hold, _) => game.hold(),
+            (Action::Pause, _) => game.toggle_pause(),
+            (Action::Restart, _) => {}
+        }
+    }
+
+    /// Runs one fixed tick: held directions repeat, soft drop speeds up
+    /// gravity and the game steps.
+    pub(crate) fn tick(&mut self, game: &mut TetrisGame) {
+        // held directions keep charging through line clears
+        self.shift(game, TICK_MS);
+        game.set_soft_drop(if self.soft_drop { self.handling.soft_drop_factor } else { 1.0 });
+        game.step();
+    }
+
+    /// Lets go of everything held, for when the page loses focus and the
+    /// releases go elsewhere.
+    pub(crate) fn release_all(&mut self, game: &mut TetrisGame) {
+        self.auto_shift = AutoShift::new();
+        self.soft_drop = false;
+        self.rotations.clear();
+        self.holding = false;
+        game.set_initial_actions(0, false);
+        game.set_soft_drop(1.0);
+    }
+
+    /// Keeps track of the rotation and hold keys held down, which turn or
+    /// hold the next piece when held during a line clear or entry delay.
+    fn track_held(&mut self, event: &InputEvent) {
+        match event.action {
+            Action::RotateClockwise | Action::RotateCounterclockwise | Action::Rotate180 => {
+                self.rotations.retain(|action| *action != event.action);
+                if event.kind == InputKind::Press {
+                    self.rotations.push(event.action);
+                }
+            }
+            Action::Hold => self.holding = event.kind == InputKind::Press,
+            _ => {}
+        }
+    }
+
+    fn initial_turns(&self) -> i64 {
+        match self.rotations.last() {
+            Some(Action::RotateClockwise) => 1,
+            Some(Action::Rotate180) => 2,
+            Some(Action::RotateCounterclockwise) => 3,
+            _ => 0
+        }
+    }
+
+    fn shift(&mut self, game: &mut TetrisGame, elapsed: f64) {
+        if let Some((direction, steps)) = self.auto_shift.update(elapsed, &self.handling) {
+            for _ in 0..steps {
+                let moved = match direction {
+                    Direction::Left => game.move_left(),
+                    Direction::Right => game.move_right()
+                };
+                if !moved {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_config::GameConfig;
+
+    fn playing() -> TetrisGame {
+        let mut game = TetrisGame::new(1234, GameConfig::default());
+        game.start();
+        game
+    }
+
+    fn press(action: Action) -> InputEvent {
+        InputEvent { action, kind: InputKind::Press, time: 0.0 }
+    }
+
+    fn release(action: Action) -> InputEvent {
+        InputEvent { action, kind: InputKind::Release, time: 0.0 }
+    }
+
+    #[test]
+    fn queue_drains_in_order_up_to_a_time() {
+        let mut queue = InputQueue::new();
+        queue.push(Action::MoveLeft, InputKind::Press, 1.0);
+        queue.push(Action::RotateClockwise, InputKind::Press, 2.0);
+        queue.push(Action::MoveLeft, InputKind::Release, 20.0);
+        let actions: Vec<Action> = queue.drain_until(10.0).iter().map(|event| event.action).collect();
+        assert_eq!(actions, vec![Action::MoveLeft, Action::RotateClockwise]);
+        assert!(queue.drain_until(19.0).is_empty());
+        assert_eq!(queue.drain_until(20.0).len(), 1);
+    }
+
+    #[test]
+    fn actions_round_trip_through_their_names() {
+        for action in Action::ALL.iter() {
+            assert_eq!(Action::from_name(&format!("{:?}", action)), Some(*action));
+        }
+        assert_eq!(Action::from_name("Fly"), None);
+    }
+
+    #[test]
+    fn late_events_are_queued_by_time() {
+        let mut queue = InputQueue::new();
+        queue.push(Action::RotateClockwise, InputKind::Press, 5.0);
+        queue.push(Action::MoveLeft, InputKind::Press, 3.0);
+        let actions: Vec<Action> = queue.drain_until(4.0).iter().map(|event| event.action).collect();
+        assert_eq!(actions, vec![Action::MoveLeft]);
+    }
+
+    #[test]
+    fn move_and_rotate_in_the_same_tick_both_apply() {
+        let mut game = playing();
+        let start = game.active_piece.clone().unwrap();
+        let mut controller = Controller::new(Handling::default());
+        controller.apply(&mut game, &press(Action::MoveLeft));
+        controller.apply(&mut game, &press(Action::RotateClockwise));
+        controller.tick(&mut game);
+        let piece = game.active_piece.clone().unwrap();
+        assert_eq!(piece.rotation, 1);
+        assert_eq!(piece.x, start.x - 1);
+    }
+
+    #[test]
+    fn releases_do_not_rotate_or_drop() {
+        let mut game = playing();
+        let start = game.active_piece.clone();
+        let mut controller = Controller::new(Handling::default());
+        for action in &[Action::RotateClockwise, Action::HardDrop, Action::Hold, Action::Pause] {
+            controller.apply(&mut game, &release(*action));
+        }
+        assert_eq!(game.active_piece, start);
+        assert_eq!(game.score, 0);
+    }
+
+    #[test]
+    fn zero_arr_charge_carries_over_to_the_next_piece() {
+        let mut game = playing();
+        let width = game.config.width;
+        let at_wall = |game: &TetrisGame| {
+            game.active_piece.as_ref().is_some_and(|piece| piece.parts.iter().any(|part| part.x == width - 1))
+        };
+        let mut controller = Controller::new(Handling { das: 100.0, arr: 0.0, ..Handling::default() });
+        controller.apply(&mut game, &press(Action::MoveRight));
+        for _ in 0..10 {
+            controller.tick(&mut game);
+        }
+        assert!(at_wall(&game));
+        game.hard_drop();
+        for _ in 0..3 {
+            controller.tick(&mut game);
+        }
+        assert!(at_wall(&game));
+    }
+
+    #[test]
+    fn rotation_and_hold_held_through_a_hard_drop_leave_the_next_piece() {
+        let mut game = playing();
+        let mut controller = Controller::new(Handling::default());
+        controller.apply(&mut game, &press(Action::RotateCounterclockwise));
+        let next = game.next_pieces[0].piece_type;
+        controller.apply(&mut game, &press(Action::HardDrop));
+        let piece = game.active_piece.clone().unwrap();
+        assert_eq!((piece.piece_type, piece.rotation), (next, 0));
+        controller.apply(&mut game, &release(Action::RotateCounterclockwise));
+
+        controller.apply(&mut game, &press(Action::Hold));
+        let held = game.hold_piece.clone();
+        let next = game.next_pieces[0].piece_type;
+        controller.apply(&mut game, &press(Action::HardDrop));
+        assert_eq!(game.hold_piece, held);
+        assert_eq!(game.active_piece.as_ref().map(|piece| piece.piece_type), Some(next));
+        assert!(game.can_hold);
+    }
+
+    #[test]
+    fn rotation_and_hold_during_the_entry_delay_apply_to_the_next_piece() {
+        let config = GameConfig { entry_delay: 100.0, ..GameConfig::default() };
+        let mut game = TetrisGame::new(1234, config);
+        game.start();
+        let mut controller = Controller::new(Handling::default());
+        // held into the delay and let go before the piece enters
+        controller.apply(&mut game, &press(Action::RotateCounterclockwise));
+        controller.apply(&mut game, &press(Action::HardDrop));
+        controller.apply(&mut game, &release(Action::RotateCounterclockwise));
+        while game.active_piece.is_none() {
+            controller.tick(&mut game);
+        }
+        assert_eq!(game.active_piece.as_ref().map(|piece| piece.rotation), Some(3));
+
+        // pressed and let go during the delay
+        controller.apply(&mut game, &press(Action::HardDrop));
+        let next = game.next_pieces[0].piece_type;
+        controller.apply(&mut game, &press(Action::Hold));
+        controller.apply(&mut game, &release(Action::Hold));
+        while game.active_piece.is_none() {
+            controller.tick(&mut game);
+        }
+        assert_eq!(game.hold_piece.as_ref().map(|piece| piece.piece_type), Some(next));
+        assert!(!game.can_hold);
+    }
+
+    #[test]
+    fn release_all_lets_go_of_held_keys() {
+        let mut game = playing();
+        let mut controller = Controller::new(Handling::default());
+        controller.apply(&mut game, &press(Action::MoveRight));
+        controller.apply(&mut game, &press(Action::SoftDrop));
+        controller.release_all(&mut game);
+        let piece = game.active_piece.clone();
+        let score = game.score;
+        for _ in 0..30 {
+            controller.tick(&mut game);
+        }
+        assert_eq!(game.active_piece, piece);
+        assert_eq!(game.score, score);
+        // pressed again, the direction is not taken for a key repeat
+        controller.apply(&mut game, &press(Action::MoveRight));
+        let moved = game.active_piece.as_ref().map(|piece| piece.x);
+        assert_eq!(moved, piece.map(|piece| piece.x + 1));
+    }
+
+    #[test]
+    fn soft_drop_lasts_while_held() {
+        let mut game = playing();
+        let mut controller = Controller::new(Handling::default());
+        controller.apply(&mut game, &press(Action::SoftDrop));
+        for _ in 0..10 {
+            controller.tick(&mut game);
+        }
+        let dropped = game.score;
+        assert!(dropped > 0);
+        controller.apply(&mut game, &release(Action::SoftDrop));
+        for _ in 0..10 {
+            controller.tick(&mut game);
+        }
+        assert_eq!(game.score, dropped);
+    }
+}
@@ -1,16 +1,14 @@
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) struct TetrisPart {
     pub(crate) x: i64,
-    pub(crate) y: i64,
-    pub(crate) visible: bool
+    pub(crate) y: i64
 }
 
 impl TetrisPart {
     pub(crate) fn new(x: i64, y: i64) -> TetrisPart {
         TetrisPart {
             x,
-            y,
-            visible: true
+            y
         }
     }
 }
\ No newline at end of file
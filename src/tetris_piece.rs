@@ -1,6 +1,6 @@
 use crate::tetris_part::TetrisPart;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum TetrisPieceType {
     Q,
     Z,
@@ -14,138 +14,122 @@ pub(crate) enum TetrisPieceType {
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct TetrisPiece {
     pub(crate) parts: Vec<TetrisPart>,
-    pub(crate) color: String,
+    pub(crate) color: &'static str,
     pub(crate) piece_type: TetrisPieceType,
-    pub(crate) rotation: i64
+    /// SRS rotation state: 0 spawn, 1 clockwise, 2 upside down, 3 counterclockwise.
+    pub(crate) rotation: i64,
+    /// Board position of the top left corner of the piece's rotation box.
+    pub(crate) x: i64,
+    pub(crate) y: i64
 }
 
-impl TetrisPiece {
+impl TetrisPieceType {
 
-    pub(crate) fn hide_row(&mut self, row: i64) {
-        let mut new_parts = vec![];
-        for part in &self.parts {
-            new_parts.push(TetrisPart {
-                x: part.x,
-                y: part.y,
-                visible: if part.y == row {
-                    false
-                } else {
-                    part.visible
-                }
-            })
+    /// Side of the square box the piece rotates in.
+    fn box_size(self) -> i64 {
+        match self {
+            TetrisPieceType::Q => 2,
+            TetrisPieceType::I => 4,
+            _ => 3
         }
-        self.parts = new_parts;
     }
 
-    pub(crate) fn lower_row(&mut self, row: i64, lower_num: i64) {
-        let mut new_parts = vec![];
-        for part in &self.parts {
-            new_parts.push(TetrisPart {
-                x: part.x,
-                y: if part.y == row {
-                    part.y + lower_num
-                } else {
-                    part.y
-                },
-                visible: part.visible
-            })
+    /// Cells of the spawn orientation inside the rotation box.
+    fn spawn_cells(self) -> [(i64, i64); 4] {
+        match self {
+            TetrisPieceType::Q => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            TetrisPieceType::Z => [(0, 0), (1, 0), (1, 1), (2, 1)],
+            TetrisPieceType::S => [(1, 0), (2, 0), (0, 1), (1, 1)],
+            TetrisPieceType::T => [(0, 1), (1, 1), (2, 1), (1, 0)],
+            TetrisPieceType::I => [(0, 1), (1, 1), (2, 1), (3, 1)],
+            TetrisPieceType::L => [(0, 1), (1, 1), (2, 1), (2, 0)],
+            TetrisPieceType::J => [(0, 0), (0, 1), (1, 1), (2, 1)]
         }
-        self.parts = new_parts;
     }
 
-    pub(crate) fn new(piece_type: TetrisPieceType, start_x: i64, color: String) -> TetrisPiece {
-        match piece_type {
-            TetrisPieceType::Q => {
-                TetrisPiece {
-                    rotation: 1,
-                    piece_type,
-                    color,
-                    parts: vec![
-                        TetrisPart::new(start_x, 0),
-                        TetrisPart::new(start_x+1, 0),
-                        TetrisPart::new(start_x, 1),
-                        TetrisPart::new(start_x+1, 1)
-                    ]
-                }
-            }
-            TetrisPieceType::Z => {
-                TetrisPiece {
-                    rotation: 1,
-                    piece_type,
-                    color,
-                    parts: vec![
-                        TetrisPart::new(start_x, 0),
-                        TetrisPart::new(start_x+1, 0),
-                        TetrisPart::new(start_x+1, 1),
-                        TetrisPart::new(start_x+2, 1)
-                    ]
-                }
-            }
-            TetrisPieceType::S => {
-                TetrisPiece {
-                    rotation: 1,
-                    piece_type,
-                    color,
-                    parts: vec![
-                        TetrisPart::new(start_x+1, 0),
-                        TetrisPart::new(start_x+2, 0),
-                        TetrisPart::new(start_x, 1),
-                        TetrisPart::new(start_x+1, 1)
-                    ]
-                }
-            }
-            TetrisPieceType::T => {
-                TetrisPiece {
-                    rotation: 1,
-                    piece_type,
-                    color,
-                    parts: vec![
-                        TetrisPart::new(start_x, 1),
-                        TetrisPart::new(start_x+1, 1),
-                        TetrisPart::new(start_x+2, 1),
-                        TetrisPart::new(start_x+1, 0)
-                    ]
-                }
-            }
-            TetrisPieceType::I => {
-                TetrisPiece {
-                    rotation: 1,
-                    piece_type,
-                    color,
-                    parts: vec![
-                        TetrisPart::new(start_x, 0),
-                        TetrisPart::new(start_x+1, 0),
-                        TetrisPart::new(start_x+2, 0),
-                        TetrisPart::new(start_x+3, 0)
-                    ]
-                }
-            }
-            TetrisPieceType::L => {
-                TetrisPiece {
-                    rotation: 1,
-                    piece_type,
-                    color,
-                    parts: vec![
-                        TetrisPart::new(start_x, 1),
-                        TetrisPart::new(start_x+1, 1),
-                        TetrisPart::new(start_x+2, 1),
-                        TetrisPart::new(start_x+2, 0)
-                    ]
-                }
-            }
-            TetrisPieceType::J => {
-                TetrisPiece {
-                    rotation: 1,
-                    piece_type,
-                    color,
-                    parts: vec![
-                        TetrisPart::new(start_x, 0),
-                        TetrisPart::new(start_x, 1),
-                        TetrisPart::new(start_x+1, 1),
-                        TetrisPart::new(start_x+2, 1)
-                    ]
-                }
+    /// Cells of the given rotation state inside the rotation box, found by
+    /// turning the spawn orientation clockwise `rotation` times.
+    fn cells(self, rotation: i64) -> [(i64, i64); 4] {
+        let last = self.box_size() - 1;
+        let mut cells = self.spawn_cells();
+        for _ in 0..rotation.rem_euclid(4) {
+            for cell in cells.iter_mut() {
+                *cell = (last - cell.1, cell.0);
             }
         }
+        cells
     }
-}
\ No newline at end of file
+}
+
+impl TetrisPiece {
+
+    fn at(piece_type: TetrisPieceType, rotation: i64, x: i64, y: i64, color: &'static str) -> TetrisPiece {
+        TetrisPiece {
+            parts: piece_type.cells(rotation).iter().map(|(cx, cy)| TetrisPart::new(x + cx, y + cy)).collect(),
+            color,
+            piece_type,
+            rotation,
+            x,
+            y
+        }
+    }
+
+    /// Returns a copy of the piece moved by `dx` columns and `dy` rows.
+    pub(crate) fn shifted(&self, dx: i64, dy: i64) -> TetrisPiece {
+        TetrisPiece {
+            parts: self.parts.iter().map(|part| TetrisPart::new(part.x + dx, part.y + dy)).collect(),
+            color: self.color,
+            piece_type: self.piece_type,
+            rotation: self.rotation,
+            x: self.x + dx,
+            y: self.y + dy
+        }
+    }
+
+    /// Returns a copy of the piece turned to `rotation` inside the same box.
+    pub(crate) fn rotated(&self, rotation: i64) -> TetrisPiece {
+        TetrisPiece::at(self.piece_type, rotation.rem_euclid(4), self.x, self.y, self.color)
+    }
+
+    /// Creates a piece in spawn orientation whose cells start at column
+    /// `start_x` and row `start_y`.
+    pub(crate) fn new(piece_type: TetrisPieceType, start_x: i64, start_y: i64, color: &'static str) -> TetrisPiece {
+        // the I piece lies in the second row of its box
+        let y = if piece_type == TetrisPieceType::I { start_y - 1 } else { start_y };
+        TetrisPiece::at(piece_type, 0, start_x, y, color)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(piece: &TetrisPiece) -> Vec<(i64, i64)> {
+        let mut cells: Vec<(i64, i64)> = piece.parts.iter().map(|part| (part.x, part.y)).collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn spawn_cells_start_at_the_given_corner() {
+        let i = TetrisPiece::new(TetrisPieceType::I, 3, 5, "");
+        assert_eq!(cells(&i), vec![(3, 5), (4, 5), (5, 5), (6, 5)]);
+        let t = TetrisPiece::new(TetrisPieceType::T, 3, 5, "");
+        assert_eq!(cells(&t), vec![(3, 6), (4, 5), (4, 6), (5, 6)]);
+    }
+
+    #[test]
+    fn rotation_states_match_srs() {
+        let t = TetrisPiece::new(TetrisPieceType::T, 0, 0, "");
+        assert_eq!(cells(&t.rotated(1)), vec![(1, 0), (1, 1), (1, 2), (2, 1)]);
+        assert_eq!(cells(&t.rotated(2)), vec![(0, 1), (1, 1), (1, 2), (2, 1)]);
+        assert_eq!(cells(&t.rotated(3)), vec![(0, 1), (1, 0), (1, 1), (1, 2)]);
+        let i = TetrisPiece::new(TetrisPieceType::I, 0, 1, "");
+        assert_eq!(cells(&i.rotated(1)), vec![(2, 0), (2, 1), (2, 2), (2, 3)]);
+        assert_eq!(cells(&i.rotated(2)), vec![(0, 2), (1, 2), (2, 2), (3, 2)]);
+        let o = TetrisPiece::new(TetrisPieceType::Q, 0, 0, "");
+        assert_eq!(cells(&o.rotated(1)), cells(&o));
+        assert_eq!(cells(&t.rotated(4)), cells(&t));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::tetris_piece::TetrisPieceType;
+use crate::tetris_rng::Rng;
+
+const ALL_PIECES: [TetrisPieceType; 7] = [
+    TetrisPieceType::Q,
+    TetrisPieceType::Z,
+    TetrisPieceType::S,
+    TetrisPieceType::T,
+    TetrisPieceType::I,
+    TetrisPieceType::L,
+    TetrisPieceType::J
+];
+
+/// Decides which piece comes next. All randomness comes from the game's
+/// `Rng` so every generator stays reproducible from the seed.
+pub(crate) trait Randomizer {
+    fn next(&mut self, rng: &mut Rng) -> TetrisPieceType;
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum RandomizerKind {
+    SevenBag,
+    FourteenBag,
+    Random,
+    Nes,
+    Tgm
+}
+
+impl RandomizerKind {
+
+    pub(crate) fn create(self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::SevenBag => Box::new(BagRandomizer::new(1)),
+            RandomizerKind::FourteenBag => Box::new(BagRandomizer::new(2)),
+            RandomizerKind::Random => Box::new(PureRandomizer),
+            RandomizerKind::Nes => Box::new(NesRandomizer::new()),
+            RandomizerKind::Tgm => Box::new(TgmRandomizer::new())
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<RandomizerKind> {
+        match name {
+            "7bag" => Some(RandomizerKind::SevenBag),
+            "14bag" => Some(RandomizerKind::FourteenBag),
+            "random" => Some(RandomizerKind::Random),
+            "nes" => Some(RandomizerKind::Nes),
+            "tgm" => Some(RandomizerKind::Tgm),
+            _ => None
+        }
+    }
+}
+
+/// Deals every piece `copies` times in random order before refilling.
+pub(crate) struct BagRandomizer {
+    copies: usize,
+    bag: Vec<TetrisPieceType>
+}
+
+impl BagRandomizer {
+
+    pub(crate) fn new(copies: usize) -> BagRandomizer {
+        BagRandomizer {
+            copies,
+            bag: vec![]
+        }
+    }
+}
+
+impl Randomizer for BagRandomizer {
+    fn next(&mut self, rng: &mut Rng) -> TetrisPieceType {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend_from_slice(&ALL_PIECES);
+            }
+        }
+        let index = rng.next_index(self.bag.len());
+        self.bag.remove(index)
+    }
+}
+
+pub(crate) struct PureRandomizer;
+
+impl Randomizer for PureRandomizer {
+    fn next(&mut self, rng: &mut Rng) -> TetrisPieceType {
+        ALL_PIECES[rng.next_index(ALL_PIECES.len())]
+    }
+}
+
+/// The NES generator rolls one of eight values; the eighth value or a repeat
+/// of the previous piece triggers a single reroll among the seven pieces.
+pub(crate) struct NesRandomizer {
+    previous: Option<TetrisPieceType>
+}
+
+impl NesRandomizer {
+
+    pub(crate) fn new() -> NesRandomizer {
+        NesRandomizer {
+            previous: None
+        }
+    }
+}
+
+impl Randomizer for NesRandomizer {
+    fn next(&mut self, rng: &mut Rng) -> TetrisPieceType {
+        let roll = rng.next_index(ALL_PIECES.len() + 1);
+        let piece = if roll == ALL_PIECES.len() || Some(ALL_PIECES[roll]) == self.previous {
+            ALL_PIECES[rng.next_index(ALL_PIECES.len())]
+        } else {
+            ALL_PIECES[roll]
+        };
+        self.previous = Some(piece);
+        piece
+    }
+}
+
+const TGM_REROLLS: usize = 6;
+
+/// TGM style generator: a roll and up to six rerolls to find a piece that is
+/// not in the last four dealt, keeping the last roll if all of them are. The
+/// history starts as Z, Z, S, S and the first piece is never S, Z or O.
+pub(crate) struct TgmRandomizer {
+    history: Vec<TetrisPieceType>,
+    first: bool
+}
+
+impl TgmRandomizer {
+
+    pub(crate) fn new() -> TgmRandomizer {
+        TgmRandomizer {
+            history: vec![TetrisPieceType::Z, TetrisPieceType::Z, TetrisPieceType::S, TetrisPieceType::S],
+            first: true
+        }
+    }
+}
+
+impl Randomizer for TgmRandomizer {
+    fn next(&mut self, rng: &mut Rng) -> TetrisPieceType {
+        let piece = if self.first {
+            self.first = false;
+            let first_pieces = [TetrisPieceType::T, TetrisPieceType::I, TetrisPieceType::L, TetrisPieceType::J];
+            first_pieces[rng.next_index(first_pieces.len())]
+        } else {
+            let mut piece = ALL_PIECES[rng.next_index(ALL_PIECES.len())];
+            for _ in 0..TGM_REROLLS {
+                if !self.history.contains(&piece) {
+                    break;
+                }
+                piece = ALL_PIECES[rng.next_index(ALL_PIECES.len())];
+            }
+            piece
+        };
+        self.history.remove(0);
+        self.history.push(piece);
+        piece
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deal(kind: RandomizerKind, count: usize) -> Vec<TetrisPieceType> {
+        let mut rng = Rng::new(99);
+        let mut randomizer = kind.create();
+        (0..count).map(|_| randomizer.next(&mut rng)).collect()
+    }
+
+    fn count(pieces: &[TetrisPieceType], piece_type: TetrisPieceType) -> usize {
+        pieces.iter().filter(|p| **p == piece_type).count()
+    }
+
+    #[test]
+    fn bags_deal_each_piece_equally() {
+        let seven = deal(RandomizerKind::SevenBag, 70);
+        let fourteen = deal(RandomizerKind::FourteenBag, 14);
+        for piece_type in &ALL_PIECES {
+            for bag in seven.chunks(7) {
+                assert_eq!(count(bag, *piece_type), 1);
+            }
+            assert_eq!(count(&fourteen, *piece_type), 2);
+        }
+    }
+
+    #[test]
+    fn pure_random_deals_every_piece() {
+        let pieces = deal(RandomizerKind::Random, 500);
+        for piece_type in &ALL_PIECES {
+            assert!(count(&pieces, *piece_type) > 0);
+        }
+    }
+
+    #[test]
+    fn nes_repeats_less_than_pure_random() {
+        let pieces = deal(RandomizerKind::Nes, 7000);
+        let repeats = pieces.windows(2).filter(|w| w[0] == w[1]).count();
+        // pure random repeats 1/7 of the time, the reroll brings it near 1/28
+        assert!(repeats < 7000 / 14, "{} repeats", repeats);
+    }
+
+    #[test]
+    fn tgm_never_starts_with_s_z_or_o() {
+        for seed in 0..50 {
+            let mut rng = Rng::new(seed);
+            let first = TgmRandomizer::new().next(&mut rng);
+            assert!(![TetrisPieceType::S, TetrisPieceType::Z, TetrisPieceType::Q].contains(&first));
+        }
+    }
+
+    #[test]
+    fn tgm_rerolls_six_times() {
+        let mut randomizer = TgmRandomizer {
+            history: vec![TetrisPieceType::Q, TetrisPieceType::Z, TetrisPieceType::S, TetrisPieceType::T],
+            first: false
+        };
+        // seed 5 rolls Q, Q, S, T, Q, Z, all in the history, then I
+        let mut rng = Rng::new(5);
+        assert_eq!(randomizer.next(&mut rng), TetrisPieceType::I);
+        let mut rolls = Rng::new(5);
+        for _ in 0..TGM_REROLLS + 1 {
+            rolls.next_index(ALL_PIECES.len());
+        }
+        assert_eq!(rng, rolls);
+    }
+
+    #[test]
+    fn same_seed_same_sequence_for_every_kind() {
+        for kind in &[RandomizerKind::SevenBag, RandomizerKind::FourteenBag, RandomizerKind::Random,
+                      RandomizerKind::Nes, RandomizerKind::Tgm] {
+            assert_eq!(deal(*kind, 50), deal(*kind, 50));
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use crate::utils::{context, hold_context, next_piece_context, score};
+use crate::tetris_piece::TetrisPiece;
+use crate::tetris_config::GameConfig;
+use crate::tetris_game::{GameState, TetrisGame, CLEARING, CLEARING_STEP};
+use crate::tetris_bindings::{key_label, KeyBindings, RebindScreen, REBIND_KEY};
+use crate::tetris_input::Action;
+
+pub const CANVAS_WIDTH: u32 = 600;
+pub const CANVAS_HEIGHT: u32 = 1000;
+pub const NP_HEIGHT: u32 = 400;
+pub const NP_WIDTH: u32 = 150;
+const NP_SECTION_HEIGHT: f64 = NP_HEIGHT as f64 / 3.0;
+const NP_V_CELL_SIZE: f64 = NP_SECTION_HEIGHT / 4.0;
+const NP_H_CELL_SIZE: f64 = NP_WIDTH as f64 / 4.0;
+pub const HOLD_HEIGHT: u32 = 156;
+pub const HOLD_WIDTH: u32 = 150;
+const HOLD_CELL_SIZE: f64 = 30.0;
+
+pub(crate) struct TetrisRenderer {
+    context: web_sys::CanvasRenderingContext2d,
+    next_piece_context: web_sys::CanvasRenderingContext2d,
+    hold_context: web_sys::CanvasRenderingContext2d,
+    score: web_sys::HtmlElement,
+    /// Side of a square cell, sized so the visible rows fit the canvas.
+    cell_size: f64,
+    /// Canvas position of the top left corner of the visible playfield.
+    x_offset: f64,
+    y_offset: f64,
+    columns: i64,
+    rows: i64,
+    hidden_rows: i64,
+    /// Draw a translucent copy of the active piece where it would land.
+    pub(crate) show_ghost: bool
+}
+
+impl TetrisRenderer {
+
+    pub(crate) fn new(config: &GameConfig) -> TetrisRenderer {
+        let canvas_width = CANVAS_WIDTH as f64;
+        let canvas_height = CANVAS_HEIGHT as f64;
+        let cell_size = (canvas_width / config.width as f64).min(canvas_height / config.visible_height as f64);
+        TetrisRenderer {
+            context: context(),
+            next_piece_context: next_piece_context(),
+            hold_context: hold_context(),
+            score: score(),
+            cell_size,
+            x_offset: (canvas_width - cell_size * config.width as f64) / 2.0,
+            y_offset: (canvas_height - cell_size * config.visible_height as f64) / 2.0,
+            columns: config.width,
+            rows: config.visible_height,
+            hidden_rows: config.hidden_rows,
+            show_ghost: true
+        }
+    }
+
+    /// Side of a cell in canvas pixels.
+    pub(crate) fn cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    /// Draws the game as it is `alpha` of the way to its next tick, so
+    /// animations stay smooth on displays faster than the tick rate.
+    pub(crate) fn draw(&self, game: &TetrisGame, alpha: f64, bindings: &KeyBindings) {
+        self.draw_game_board();
+        self.draw_pieces(game);
+        self.draw_next_pieces(game);
+        self.draw_hold_piece(game);
+        self.draw_score(game);
+        if game.clearing > 0 {
+            let clearing = (game.clearing as f64 - CLEARING_STEP as f64 * alpha).max(0.0);
+            self.draw_clearing_rows(game, clearing);
+            if game.last_action.is_some_and(|action| action.perfect_clear) {
+                self.draw_perfect_clear(clearing);
+            }
+        }
+        match game.state {
+            GameState::Paused => self.draw_paused(bindings),
+            GameState::GameOver(_) => self.draw_game_over(game, bindings),
+            GameState::Playing => {}
+        }
+    }
+
+    /// Covers the board so a paused game can not be studied.
+    fn draw_paused(&self, bindings: &KeyBindings) {
+        let context = &self.context;
+        let center = CANVAS_WIDTH as f64 / 2.0;
+        context.set_fill_style_str("rgb(0, 7, 18)");
+        context.fill_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+
+        context.set_fill_style_str("#ffffff");
+        context.set_text_align("center");
+        context.set_font("bold 72px Arial, Helvetica, serif");
+        context.fill_text("PAUSED", center, 450.0).ok();
+        context.set_font("32px Arial, Helvetica, serif");
+        let hints = [
+            bindings.key_hint(Action::Pause).map(|keys| format!("{} to resume", keys)),
+            bindings.key_hint(Action::Restart).map(|keys| format!("{} to restart", keys)),
+            Some(format!("{} to change keys", REBIND_KEY))
+        ];
+        for (line, hint) in hints.iter().flatten().enumerate() {
+            context.fill_text(hint, center, 520.0 + line as f64 * 50.0).ok();
+        }
+    }
+
+    fn draw_game_over(&self, game: &TetrisGame, bindings: &KeyBindings) {
+        let context = &self.context;
+        let center = CANVAS_WIDTH as f64 / 2.0;
+        context.set_fill_style_str("rgba(0, 7, 18, 0.8)");
+        context.fill_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+
+        context.set_fill_style_str("#ffffff");
+        context.set_text_align("center");
+        context.set_font("bold 72px Arial, Helvetica, serif");
+        context.fill_text("GAME OVER", center, 450.0).ok();
+        context.set_font("48px Arial, Helvetica, serif");
+        context.fill_text(&format!("Score: {}", game.score), center, 540.0).ok();
+        context.fill_text(&format!("Level: {}", game.level), center, 600.0).ok();
+        context.fill_text(&format!("Perfect clears: {}", game.perfect_clears), center, 660.0).ok();
+        // the seed replays the same pieces, so it is kept with the score
+        context.fill_text(&format!("Seed: {}", game.seed()), center, 720.0).ok();
+        if let Some(keys) = bindings.key_hint(Action::Restart) {
+            context.set_font("32px Arial, Helvetica, serif");
+            context.fill_text(&format!("{} to restart", keys), center, 800.0).ok();
+        }
+    }
+
+    /// Lists every action with its keys over the board, the selected one
+    /// highlighted.
+    pub(crate) fn draw_rebind_screen(&self, screen: &RebindScreen, bindings: &KeyBindings) {
+        let context = &self.context;
+        let center = CANVAS_WIDTH as f64 / 2.0;
+        context.set_fill_style_str("rgb(0, 7, 18)");
+        context.fill_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+
+        context.set_fill_style_str("#ffffff");
+        context.set_text_align("center");
+        context.set_font("bold 56px Arial, Helvetica, serif");
+        context.fill_text("KEYS", center, 120.0).ok();
+
+        context.set_font("28px Arial, Helvetica, serif");
+        for (index, action) in Action::ALL.iter().enumerate() {
+            let y = 200.0 + index as f64 * 56.0;
+            let selected = index == screen.selected;
+            if selected {
+                context.set_fill_style_str("#2a3b5c");
+                context.fill_rect(30.0, y - 36.0, CANVAS_WIDTH as f64 - 60.0, 50.0);
+            }
+            let keys = if selected && screen.capturing {
+                "press a key...".to_string()
+            } else {
+                bindings.keys(*action).iter().map(|key| key_label(key)).collect::<Vec<&str>>().join(", ")
+            };
+            context.set_fill_style_str("#ffffff");
+            context.set_text_align("left");
+            context.fill_text(action.name(), 50.0, y).ok();
+            context.set_text_align("right");
+            context.fill_text(&keys, CANVAS_WIDTH as f64 - 50.0, y).ok();
+        }
+
+        context.set_text_align("center");
+        context.set_font("24px Arial, Helvetica, serif");
+        context.fill_text("Up/Down to choose, Enter to add a key", center, 860.0).ok();
+        context.fill_text("Backspace to clear, Esc to save and close", center, 900.0).ok();
+    }
+
+    /// Announces a perfect clear over the empty board, fading out with the
+    /// line clear animation.
+    fn draw_perfect_clear(&self, clearing: f64) {
+        let context = &self.context;
+        let center = CANVAS_WIDTH as f64 / 2.0;
+        context.set_global_alpha(clearing / CLEARING as f64);
+        context.set_fill_style_str("#ffd500");
+        context.set_text_align("center");
+        context.set_font("bold 64px Arial, Helvetica, serif");
+        context.fill_text("PERFECT", center, 450.0).ok();
+        context.fill_text("CLEAR", center, 530.0).ok();
+        context.set_global_alpha(1.0);
+    }
+
+    fn draw_clearing_rows(&self, game: &TetrisGame, clearing: f64) {
+        let r = 255.0 - clearing;
+        let g = 213.0 - clearing;
+        let b = 0;
+        let color = format!("rgb({:.0}, {:.0}, {})", r, g, b);
+        self.context.set_fill_style_str(&color);
+        for line in &game.cleared_rows {
+            if *line < self.hidden_rows {
+                continue
+            }
+            let y_start = self.y_offset + (*line - self.hidden_rows) as f64 * self.cell_size;
+            self.context.fill_rect(self.x_offset, y_start, self.columns as f64 * self.cell_size, self.cell_size);
+        }
+    }
+
+    fn draw_next_pieces(&self, game: &TetrisGame) {
+        let ctx = &self.next_piece_context;
+        ctx.set_fill_style_str("#071428");
+        ctx.fill_rect(0.0, 0.0, NP_WIDTH as f64, NP_HEIGHT as f64);
+
+        let mut i = 0.0;
+        for piece in &game.next_pieces {
+            let start = i * NP_SECTION_HEIGHT;
+            let min_x = piece.parts.iter().map(|part| part.x).min().unwrap_or(0);
+            let min_y = piece.parts.iter().map(|part| part.y).min().unwrap_or(0);
+            ctx.set_fill_style_str(piece.color);
+            for part in &piece.parts {
+                let x = part.x - min_x;
+                let y = part.y - min_y;
+
+                let x_start = (x as f64 * NP_H_CELL_SIZE) + 20.0;
+                let y_start = (y as f64 * NP_V_CELL_SIZE) + start + 20.0;
+                ctx.fill_rect(x_start, y_start, NP_H_CELL_SIZE, NP_V_CELL_SIZE);
+            }
+            i += 1.0;
+        }
+    }
+
+    fn draw_hold_piece(&self, game: &TetrisGame) {
+        let ctx = &self.hold_context;
+        ctx.set_fill_style_str("#071428");
+        ctx.fill_rect(0.0, 0.0, HOLD_WIDTH as f64, HOLD_HEIGHT as f64);
+        if let Some(piece) = &game.hold_piece {
+            // dim the piece while it can not be swapped back in
+            ctx.set_global_alpha(if game.can_hold { 1.0 } else { 0.4 });
+            self.draw_centered(ctx, piece, HOLD_WIDTH as f64, HOLD_HEIGHT as f64, HOLD_CELL_SIZE);
+            ctx.set_global_alpha(1.0);
+        }
+    }
+
+    /// Draws the piece in the middle of a `width` by `height` area.
+    fn draw_centered(&self, ctx: &web_sys::CanvasRenderingContext2d, piece: &TetrisPiece, width: f64, height: f64, cell_size: f64) {
+        let min_x = piece.parts.iter().map(|part| part.x).min().unwrap_or(0);
+        let max_x = piece.parts.iter().map(|part| part.x).max().unwrap_or(0);
+        let min_y = piece.parts.iter().map(|part| part.y).min().unwrap_or(0);
+        let max_y = piece.parts.iter().map(|part| part.y).max().unwrap_or(0);
+        let left = (width - (max_x - min_x + 1) as f64 * cell_size) / 2.0;
+        let top = (height - (max_y - min_y + 1) as f64 * cell_size) / 2.0;
+        ctx.set_fill_style_str(piece.color);
+        for part in &piece.parts {
+            let x_start = left + (part.x - min_x) as f64 * cell_size;
+            let y_start = top + (part.y - min_y) as f64 * cell_size;
+            ctx.fill_rect(x_start, y_start, cell_size, cell_size);
+        }
+    }
+
+    fn draw_score(&self, game: &TetrisGame) {
+        let action = game.last_action.map(|action| action.name()).unwrap_or_default();
+        let combo = match game.combo {
+            Some(combo) if combo > 0 => format!("Combo {}", combo),
+            _ => String::new()
+        };
+        let back_to_back = if game.back_to_back { "B2B" } else { "" };
+        self.score.set_inner_html(&format!(
+            "{}<small>Level {}</small><small>Lines {}</small><small>PC {}</small><small>{}</small><small>{} {}</small>",
+            game.score, game.level, game.lines, game.perfect_clears, action, back_to_back, combo
+        ));
+    }
+
+    /// Fills the cell at board coordinates, skipping the hidden rows.
+    fn draw_cell(&self, x: i64, y: i64, color: &str) {
+        if y < self.hidden_rows {
+            return;
+        }
+        let x_start = self.x_offset + (x as f64) * self.cell_size;
+        let y_start = self.y_offset + ((y - self.hidden_rows) as f64) * self.cell_size;
+        self.context.set_fill_style_str(color);
+        self.context.fill_rect(x_start, y_start, self.cell_size, self.cell_size);
+    }
+
+    fn draw_pieces(&self, game: &TetrisGame) {
+        let board = &game.board;
+        for y in self.hidden_rows..board.height() {
+            for x in 0..board.width() {
+                if let Some(cell) = board.cell(x, y) {
+                    self.draw_cell(x, y, cell.color);
+                }
+            }
+        }
+        if self.show_ghost {
+            if let Some(ghost) = game.ghost_piece() {
+                self.context.set_global_alpha(0.3);
+                for part in &ghost.parts {
+                    self.draw_cell(part.x, part.y, ghost.color);
+                }
+                self.context.set_global_alpha(1.0);
+            }
+        }
+        if let Some(piece) = &game.active_piece {
+            for part in &piece.parts {
+                self.draw_cell(part.x, part.y, piece.color);
+            }
+        }
+    }
+
+    fn draw_game_board(&self) {
+        let context = &self.context;
+        let board_width = self.cell_size * self.columns as f64;
+        let board_height = self.cell_size * self.rows as f64;
+        context.set_fill_style_str("#071428");
+        context.fill_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+        context.set_fill_style_str("#000712");
+        context.fill_rect(self.x_offset, self.y_offset, board_width, board_height);
+
+        context.set_stroke_style_str("#0654df");
+        for n in 0..self.rows+1 {
+            let y = self.y_offset + self.cell_size * (n as f64);
+            context.begin_path();
+            context.move_to(self.x_offset, y);
+            context.line_to(self.x_offset + board_width, y);
+            context.stroke();
+        }
+
+        for n in 0..self.columns+1 {
+            let x = self.x_offset + self.cell_size * (n as f64);
+            context.begin_path();
+            context.move_to(x, self.y_offset);
+            context.line_to(x, self.y_offset + board_height);
+            context.stroke();
+        }
+    }
+}
@@ -0,0 +1,78 @@
+/// Small seedable PRNG (xorshift64*) so that a game can be replayed from its
+/// seed alone. Not suitable for anything that needs real randomness.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Rng {
+    seed: u64,
+    state: u64
+}
+
+impl Rng {
+
+    pub(crate) fn new(seed: u64) -> Rng {
+        // xorshift never leaves the all zero state, so scramble the seed first
+        let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        state ^= state >> 31;
+        Rng {
+            seed,
+            state: if state == 0 { 1 } else { state }
+        }
+    }
+
+    pub(crate) fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniform value in `[0, 1)`.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform index in `0..len`.
+    pub(crate) fn next_index(&mut self, len: usize) -> usize {
+        (self.next_f64() * len as f64) as usize
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_eq!(a.seed(), 42);
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        let mut a = Rng::new(0);
+        let mut b = Rng::new(1);
+        let a: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        let b: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn index_stays_in_range() {
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            assert!(rng.next_index(7) < 7);
+            let f = rng.next_f64();
+            assert!((0.0..1.0).contains(&f));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::tetris_piece::TetrisPieceType;
+
+/// Super Rotation System wall kicks. Offsets are `(dx, dy)` with `dy`
+/// growing downwards like board rows, tried in order until one fits.
+/// Rows are indexed by the rotation state the piece turns from.
+type KickTable = [[(i64, i64); 5]; 4];
+type HalfTurnKickTable = [[(i64, i64); 6]; 4];
+
+const JLSTZ_CLOCKWISE: KickTable = [
+    // 0 -> R
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    // R -> 2
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    // 2 -> L
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    // L -> 0
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]
+];
+
+const I_CLOCKWISE: KickTable = [
+    // 0 -> R
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    // R -> 2
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    // 2 -> L
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    // L -> 0
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)]
+];
+
+const JLSTZ_COUNTERCLOCKWISE: KickTable = [
+    // 0 -> L
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    // R -> 0
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    // 2 -> R
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    // L -> 2
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]
+];
+
+const I_COUNTERCLOCKWISE: KickTable = [
+    // 0 -> L
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    // R -> 0
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    // 2 -> R
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+    // L -> 2
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)]
+];
+
+/// SRS has no half turns, these are the widely used SRS+ kicks and apply to
+/// every piece.
+const HALF_TURN: HalfTurnKickTable = [
+    // 0 -> 2
+    [(0, 0), (0, -1), (1, -1), (-1, -1), (1, 0), (-1, 0)],
+    // R -> L
+    [(0, 0), (1, 0), (1, -2), (1, -1), (0, -2), (0, -1)],
+    // 2 -> 0
+    [(0, 0), (0, 1), (-1, 1), (1, 1), (-1, 0), (1, 0)],
+    // L -> R
+    [(0, 0), (-1, 0), (-1, -2), (-1, -1), (0, -2), (0, -1)]
+];
+
+/// Kicks to try when turning out of rotation state `from` by `turns`
+/// quarter turns clockwise (1 clockwise, 2 half turn, 3 counterclockwise).
+/// The O piece never moves when rotating.
+pub(crate) fn kicks(piece_type: TetrisPieceType, from: i64, turns: i64) -> &'static [(i64, i64)] {
+    let from = from.rem_euclid(4) as usize;
+    match (piece_type, turns.rem_euclid(4)) {
+        (TetrisPieceType::Q, _) | (_, 0) => &[(0, 0)],
+        (_, 2) => &HALF_TURN[from],
+        (TetrisPieceType::I, 1) => &I_CLOCKWISE[from],
+        (TetrisPieceType::I, _) => &I_COUNTERCLOCKWISE[from],
+        (_, 1) => &JLSTZ_CLOCKWISE[from],
+        (_, _) => &JLSTZ_COUNTERCLOCKWISE[from]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_rotation_tries_in_place_first() {
+        for piece_type in &[TetrisPieceType::Q, TetrisPieceType::T, TetrisPieceType::I] {
+            for from in 0..4 {
+                for turns in 1..4 {
+                    assert_eq!(kicks(*piece_type, from, turns)[0], (0, 0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn counterclockwise_undoes_clockwise() {
+        // turning back must try the opposite offsets of turning forward
+        for piece_type in &[TetrisPieceType::T, TetrisPieceType::I] {
+            for from in 0..4 {
+                let forward = kicks(*piece_type, from, 1);
+                let back = kicks(*piece_type, from + 1, 3);
+                for (f, b) in forward.iter().zip(back) {
+                    assert_eq!((f.0, f.1), (-b.0, -b.1));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn o_piece_does_not_kick() {
+        for turns in 1..4 {
+            assert_eq!(kicks(TetrisPieceType::Q, 2, turns).len(), 1);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::tetris_spin::TSpin;
+
+/// What the last locked piece achieved: the lines it cleared, whether it
+/// was a T-spin, whether it followed another difficult clear and whether it
+/// left the board empty.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct ClearAction {
+    pub(crate) lines: usize,
+    pub(crate) t_spin: Option<TSpin>,
+    pub(crate) back_to_back: bool,
+    pub(crate) perfect_clear: bool
+}
+
+impl ClearAction {
+
+    /// Tetrises and T-spins that clear lines keep a back-to-back chain
+    /// going, any other line clear breaks it.
+    pub(crate) fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.lines > 0 && self.t_spin.is_some())
+    }
+
+    /// Guideline points before the level multiplier, half again as much
+    /// for a back-to-back clear, plus the perfect clear bonus.
+    pub(crate) fn points(&self) -> i64 {
+        let points = self.base_points();
+        let points = if self.back_to_back { points * 3 / 2 } else { points };
+        points + self.perfect_clear_points()
+    }
+
+    fn perfect_clear_points(&self) -> i64 {
+        if !self.perfect_clear {
+            return 0;
+        }
+        match self.lines {
+            0 => 0,
+            1 => 800,
+            2 => 1200,
+            3 => 1800,
+            _ if self.back_to_back => 3200,
+            _ => 2000
+        }
+    }
+
+    fn base_points(&self) -> i64 {
+        match (self.t_spin, self.lines) {
+            (None, 0) => 0,
+            (None, 1) => 100,
+            (None, 2) => 300,
+            (None, 3) => 500,
+            (None, _) => 800,
+            (Some(TSpin::Mini), 0) => 100,
+            (Some(TSpin::Mini), 1) => 200,
+            (Some(TSpin::Mini), _) => 400,
+            (Some(TSpin::Full), 0) => 400,
+            (Some(TSpin::Full), 1) => 800,
+            (Some(TSpin::Full), 2) => 1200,
+            (Some(TSpin::Full), _) => 1600
+        }
+    }
+
+    /// Name shown to the player, e.g. `T-Spin Mini Single` or `Tetris`.
+    pub(crate) fn name(&self) -> String {
+        let lines = match self.lines {
+            0 => "",
+            1 => "Single",
+            2 => "Double",
+            3 => "Triple",
+            _ => "Tetris"
+        };
+        let name = match self.t_spin {
+            None => lines.to_string(),
+            Some(TSpin::Mini) => format!("T-Spin Mini {}", lines).trim_end().to_string(),
+            Some(TSpin::Full) => format!("T-Spin {}", lines).trim_end().to_string()
+        };
+        let name = if self.back_to_back { format!("B2B {}", name) } else { name };
+        if self.perfect_clear { format!("{} Perfect Clear", name) } else { name }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(lines: usize, t_spin: Option<TSpin>) -> ClearAction {
+        ClearAction { lines, t_spin, back_to_back: false, perfect_clear: false }
+    }
+
+    #[test]
+    fn guideline_points() {
+        assert_eq!(action(0, None).points(), 0);
+        assert_eq!(action(4, None).points(), 800);
+        assert_eq!(action(0, Some(TSpin::Mini)).points(), 100);
+        assert_eq!(action(1, Some(TSpin::Mini)).points(), 200);
+        assert_eq!(action(0, Some(TSpin::Full)).points(), 400);
+        assert_eq!(action(2, Some(TSpin::Full)).points(), 1200);
+        assert_eq!(action(3, Some(TSpin::Full)).points(), 1600);
+        let back_to_back = ClearAction { back_to_back: true, ..action(4, None) };
+        assert_eq!(back_to_back.points(), 1200);
+    }
+
+    #[test]
+    fn perfect_clears_add_a_bonus() {
+        let single = ClearAction { perfect_clear: true, ..action(1, None) };
+        assert_eq!(single.points(), 100 + 800);
+        let tetris = ClearAction { perfect_clear: true, ..action(4, None) };
+        assert_eq!(tetris.points(), 800 + 2000);
+        let back_to_back = ClearAction { back_to_back: true, ..tetris };
+        assert_eq!(back_to_back.points(), 1200 + 3200);
+        assert_eq!(back_to_back.name(), "B2B Tetris Perfect Clear");
+    }
+
+    #[test]
+    fn only_tetrises_and_clearing_t_spins_are_difficult() {
+        assert!(action(4, None).is_difficult());
+        assert!(action(1, Some(TSpin::Mini)).is_difficult());
+        assert!(!action(3, None).is_difficult());
+        assert!(!action(0, Some(TSpin::Full)).is_difficult());
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(action(4, None).name(), "Tetris");
+        assert_eq!(action(0, Some(TSpin::Full)).name(), "T-Spin");
+        assert_eq!(action(1, Some(TSpin::Mini)).name(), "T-Spin Mini Single");
+        assert_eq!(action(3, Some(TSpin::Full)).name(), "T-Spin Triple");
+        assert_eq!(ClearAction { back_to_back: true, ..action(4, None) }.name(), "B2B Tetris");
+    }
+}
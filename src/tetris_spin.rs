@@ -0,0 +1,83 @@
+use crate::tetris_board::Board;
+use crate::tetris_piece::{TetrisPiece, TetrisPieceType};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum TSpin {
+    Mini,
+    Full
+}
+
+/// Corners of the T piece's 3x3 box, the first two on the side it points to.
+fn corners(piece: &TetrisPiece) -> [(i64, i64); 4] {
+    let (left, right, top, bottom) = (piece.x, piece.x + 2, piece.y, piece.y + 2);
+    match piece.rotation.rem_euclid(4) {
+        0 => [(left, top), (right, top), (left, bottom), (right, bottom)],
+        1 => [(right, top), (right, bottom), (left, top), (left, bottom)],
+        2 => [(left, bottom), (right, bottom), (left, top), (right, top)],
+        _ => [(left, top), (left, bottom), (right, top), (right, bottom)]
+    }
+}
+
+/// Classifies a T piece locking right after a rotation with the 3-corner
+/// rule: three occupied corners make a T-spin, a full one when both corners
+/// it points to are taken. A rotation that needed the last SRS kick (the
+/// TST kick) always counts as full.
+pub(crate) fn t_spin(board: &Board, piece: &TetrisPiece, tst_kick: bool) -> Option<TSpin> {
+    if piece.piece_type != TetrisPieceType::T {
+        return None;
+    }
+    let taken: Vec<bool> = corners(piece).iter().map(|(x, y)| board.is_occupied(*x, *y)).collect();
+    if taken.iter().filter(|corner| **corner).count() < 3 {
+        None
+    } else if (taken[0] && taken[1]) || tst_kick {
+        Some(TSpin::Full)
+    } else {
+        Some(TSpin::Mini)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris_part::TetrisPart;
+
+    fn board_with(cells: &[(i64, i64)]) -> Board {
+        let mut board = Board::new(10, 10);
+        board.lock(&TetrisPiece {
+            parts: cells.iter().map(|(x, y)| TetrisPart::new(*x, *y)).collect(),
+            color: "",
+            piece_type: TetrisPieceType::Q,
+            rotation: 0,
+            x: 0,
+            y: 0
+        });
+        board
+    }
+
+    #[test]
+    fn three_corners_with_both_front_corners_is_full() {
+        // T pointing down into a slot, both lower corners and one upper taken
+        let t = TetrisPiece::new(TetrisPieceType::T, 3, 5, "").rotated(2);
+        let board = board_with(&[(3, 7), (5, 7), (3, 5)]);
+        assert_eq!(t_spin(&board, &t, false), Some(TSpin::Full));
+    }
+
+    #[test]
+    fn one_front_corner_is_mini_unless_tst_kicked() {
+        let t = TetrisPiece::new(TetrisPieceType::T, 3, 5, "").rotated(2);
+        let board = board_with(&[(3, 7), (3, 5), (5, 5)]);
+        assert_eq!(t_spin(&board, &t, false), Some(TSpin::Mini));
+        assert_eq!(t_spin(&board, &t, true), Some(TSpin::Full));
+    }
+
+    #[test]
+    fn walls_count_as_corners_and_two_corners_are_not_enough() {
+        // against the left wall both left corners are taken
+        let t = TetrisPiece::new(TetrisPieceType::T, -1, 5, "").rotated(1);
+        assert_eq!(t_spin(&board_with(&[]), &t, false), None);
+        assert_eq!(t_spin(&board_with(&[(1, 7)]), &t, false), Some(TSpin::Mini));
+        let s = TetrisPiece::new(TetrisPieceType::S, 3, 5, "");
+        assert_eq!(t_spin(&board_with(&[(3, 5), (5, 5), (3, 7)]), &s, false), None);
+    }
+}
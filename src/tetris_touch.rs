@@ -0,0 +1,143 @@
+use crate::tetris_input::Action;
+
+/// Longest press, in milliseconds, that still counts as a tap.
+const TAP_TIME: f64 = 250.0;
+/// How far, in cells, a finger may wander during a tap.
+const TAP_DISTANCE: f64 = 0.5;
+/// A hard drop swipe covers at least this many cells down, at no less than
+/// this many cells per millisecond.
+const SWIPE_DISTANCE: f64 = 2.0;
+const SWIPE_SPEED: f64 = 0.02;
+
+struct Touch {
+    id: i32,
+    start_x: f64,
+    start_y: f64,
+    start_time: f64,
+    /// Where the last whole cell of horizontal drag ended.
+    anchor_x: f64,
+    moved: bool
+}
+
+/// Turns one finger on the board into actions: a tap rotates, dragging
+/// sideways moves a cell at a time and a quick swipe down hard drops.
+/// Coordinates are in page pixels, `cell_size` is a cell's width on screen.
+pub(crate) struct TouchTracker {
+    cell_size: f64,
+    touch: Option<Touch>
+}
+
+impl TouchTracker {
+
+    pub(crate) fn new() -> TouchTracker {
+        TouchTracker {
+            cell_size: 1.0,
+            touch: None
+        }
+    }
+
+    /// Follows a new finger unless one is already down.
+    pub(crate) fn start(&mut self, id: i32, x: f64, y: f64, time: f64, cell_size: f64) {
+        if self.touch.is_some() {
+            return;
+        }
+        self.cell_size = cell_size.max(1.0);
+        self.touch = Some(Touch { id, start_x: x, start_y: y, start_time: time, anchor_x: x, moved: false });
+    }
+
+    /// Returns a move for every whole cell dragged since the last one.
+    pub(crate) fn move_to(&mut self, id: i32, x: f64, y: f64) -> Vec<Action> {
+        let cell_size = self.cell_size;
+        let touch = match self.touch.as_mut() {
+            Some(touch) if touch.id == id => touch,
+            _ => return Vec::new()
+        };
+        let cells = ((x - touch.anchor_x) / cell_size).trunc();
+        touch.anchor_x += cells * cell_size;
+        if cells != 0.0 || (y - touch.start_y).abs() > TAP_DISTANCE * cell_size {
+            touch.moved = true;
+        }
+        let action = if cells < 0.0 { Action::MoveLeft } else { Action::MoveRight };
+        vec![action; cells.abs() as usize]
+    }
+
+    /// Lifts the finger, returning the last cells dragged and a rotation
+    /// for a tap or a hard drop for a swipe down.
+    pub(crate) fn end(&mut self, id: i32, x: f64, y: f64, time: f64) -> Vec<Action> {
+        let mut actions = self.move_to(id, x, y);
+        let touch = match self.touch.take() {
+            Some(touch) if touch.id == id => touch,
+            other => {
+                self.touch = other;
+                return actions;
+            }
+        };
+        let (dx, dy) = (x - touch.start_x, y - touch.start_y);
+        let duration = (time - touch.start_time).max(1.0);
+        if !touch.moved && duration <= TAP_TIME {
+            actions.push(Action::RotateClockwise);
+        } else if dy >= SWIPE_DISTANCE * self.cell_size
+            && dy > dx.abs()
+            && dy / duration >= SWIPE_SPEED * self.cell_size {
+            actions.push(Action::HardDrop);
+        }
+        actions
+    }
+
+    pub(crate) fn cancel(&mut self) {
+        self.touch = None;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: f64 = 40.0;
+
+    fn touched(x: f64, y: f64) -> TouchTracker {
+        let mut tracker = TouchTracker::new();
+        tracker.start(1, x, y, 0.0, CELL);
+        tracker
+    }
+
+    #[test]
+    fn a_short_still_touch_is_a_tap() {
+        let mut tracker = touched(100.0, 100.0);
+        assert_eq!(tracker.end(1, 105.0, 103.0, 120.0), vec![Action::RotateClockwise]);
+        let mut tracker = touched(100.0, 100.0);
+        assert!(tracker.end(1, 100.0, 100.0, 600.0).is_empty());
+    }
+
+    #[test]
+    fn dragging_moves_a_cell_at_a_time() {
+        let mut tracker = touched(100.0, 100.0);
+        assert!(tracker.move_to(1, 130.0, 100.0).is_empty());
+        assert_eq!(tracker.move_to(1, 150.0, 100.0), vec![Action::MoveRight]);
+        assert_eq!(tracker.move_to(1, 55.0, 100.0), vec![Action::MoveLeft, Action::MoveLeft]);
+        // the drag is not a tap, however quickly it ends
+        assert!(tracker.end(1, 60.0, 100.0, 100.0).is_empty());
+        let mut tracker = touched(100.0, 100.0);
+        assert_eq!(tracker.end(1, 145.0, 100.0, 100.0), vec![Action::MoveRight]);
+    }
+
+    #[test]
+    fn a_quick_swipe_down_hard_drops() {
+        let mut tracker = touched(100.0, 100.0);
+        tracker.move_to(1, 102.0, 200.0);
+        assert_eq!(tracker.end(1, 102.0, 220.0, 100.0), vec![Action::HardDrop]);
+        let mut tracker = touched(100.0, 100.0);
+        tracker.move_to(1, 102.0, 200.0);
+        assert!(tracker.end(1, 102.0, 220.0, 1000.0).is_empty());
+    }
+
+    #[test]
+    fn other_fingers_are_ignored() {
+        let mut tracker = touched(100.0, 100.0);
+        tracker.start(2, 300.0, 300.0, 10.0, CELL);
+        assert!(tracker.move_to(2, 400.0, 300.0).is_empty());
+        assert!(tracker.end(2, 300.0, 300.0, 20.0).is_empty());
+        assert_eq!(tracker.end(1, 100.0, 100.0, 30.0), vec![Action::RotateClockwise]);
+    }
+}
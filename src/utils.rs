@@ -1,5 +1,6 @@
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
+use std::any::Any;
 
 pub fn window() -> web_sys::Window {
     web_sys::window().expect("no global `window` exists")
@@ -11,6 +12,45 @@ pub fn request_animation_frame(f: &Closure<dyn FnMut()>) {
         .expect("should register `requestAnimationFrame` OK");
 }
 
+/// Event listeners that stay registered while this value lives. Dropping
+/// it removes them and frees their closures.
+pub struct Listeners {
+    registered: Vec<(web_sys::EventTarget, &'static str, js_sys::Function)>,
+    closures: Vec<Box<dyn Any>>
+}
+
+impl Listeners {
+
+    pub fn new() -> Listeners {
+        Listeners {
+            registered: Vec::new(),
+            closures: Vec::new()
+        }
+    }
+
+    /// Calls `closure` for each of `events` on every one of `targets`.
+    pub fn listen<T: ?Sized + 'static>(&mut self, targets: &[web_sys::EventTarget], events: &[&'static str], closure: Closure<T>) -> Result<(), JsValue> {
+        let callback = closure.as_ref().unchecked_ref::<js_sys::Function>().clone();
+        self.closures.push(Box::new(closure));
+        for target in targets {
+            for event in events {
+                target.add_event_listener_with_callback(event, &callback)?;
+                self.registered.push((target.clone(), event, callback.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Listeners {
+    fn drop(&mut self) {
+        for (target, event, callback) in &self.registered {
+            // only fails for targets that are not event targets
+            let _ = target.remove_event_listener_with_callback(event, callback);
+        }
+    }
+}
+
 pub fn document() -> web_sys::Document {
     window()
         .document()
@@ -26,6 +66,26 @@ pub fn score() -> web_sys::HtmlElement {
     document().get_element_by_id("score").unwrap().dyn_into::<web_sys::HtmlElement>().unwrap()
 }
 
+/// Reads `?<name>=<value>` from the page URL, e.g. `?seed=42&randomizer=tgm`.
+pub fn query_param(name: &str) -> Option<String> {
+    let search = window().location().search().ok()?;
+    let prefix = format!("{}=", name);
+    search.trim_start_matches('?')
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(prefix.as_str()))
+        .map(|value| value.to_string())
+}
+
+/// Reads an on/off URL parameter such as `?ghost=0` or `?half_turns=true`.
+pub fn flag_param(name: &str) -> Option<bool> {
+    query_param(name).map(|value| value == "1" || value == "true")
+}
+
+/// The page's `localStorage`, missing when the browser blocks it.
+pub fn local_storage() -> Option<web_sys::Storage> {
+    window().local_storage().ok().flatten()
+}
+
 pub fn canvas() -> web_sys::HtmlCanvasElement {
     document().get_element_by_id("canvas")
         .unwrap()
@@ -60,6 +120,23 @@ pub fn next_piece_context() -> web_sys::CanvasRenderingContext2d {
         .unwrap()
 }
 
+pub fn hold_canvas() -> web_sys::HtmlCanvasElement {
+    document().get_element_by_id("hold-canvas")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|_| ())
+        .unwrap()
+}
+
+pub fn hold_context() -> web_sys::CanvasRenderingContext2d {
+    hold_canvas()
+        .get_context("2d")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<web_sys::CanvasRenderingContext2d>()
+        .unwrap()
+}
+
 #[macro_export]
 macro_rules! log {
     ( $( $t:tt )* ) => {